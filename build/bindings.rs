@@ -0,0 +1,557 @@
+//! Turns reflected shader modules into Rust source, see `src/shaders.rs` for the types the
+//! generated code refers to.
+
+use super::reflect::{DescriptorKind, Module, Stage, Type};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Generates one `pub mod` per shader, named after the shader file.
+pub fn generate(modules: &[(String, Module)]) -> Result<String, String> {
+    let mut out = String::new();
+    for (name, module) in modules {
+        module_source(&mut out, name, module)
+            .map_err(|e| format!("{}: couldn't generate bindings: {}", name, e))?;
+    }
+    Ok(out)
+}
+
+/// The list of shader names and, when `embed` is set, an `include_bytes!` of each compiled
+/// module in `spirv_dir`.
+pub fn spirv_table(names: &[&str], spirv_dir: &Path, embed: bool) -> String {
+    let mut out = String::new();
+    writeln!(out, "/// The name of every compiled shader.").unwrap();
+    writeln!(out, "pub const SHADER_NAMES: &[&str] = &{:?};", names).unwrap();
+    if embed {
+        writeln!(out).unwrap();
+        writeln!(out, "/// Compiled SPIR-V keyed by shader name.").unwrap();
+        writeln!(out, "static EMBEDDED_SPIRV: &[(&str, &[u8])] = &[").unwrap();
+        for name in names {
+            let path = spirv_dir.join(format!("{}.spv", name));
+            writeln!(out, "    ({:?}, include_bytes!({:?})),", name, path.display().to_string())
+                .unwrap();
+        }
+        writeln!(out, "];").unwrap();
+    }
+    out
+}
+
+/// The identifier of the generated module for a shader, `simple.frag` becomes `simple_frag`.
+pub fn module_name(shader: &str) -> String {
+    snake_case(shader)
+}
+
+fn module_source(out: &mut String, name: &str, module: &Module) -> Result<(), String> {
+    let stage = match module.stage {
+        Some(Stage::Vertex) => "VERTEX",
+        Some(Stage::Hull) => "HULL",
+        Some(Stage::Domain) => "DOMAIN",
+        Some(Stage::Geometry) => "GEOMETRY",
+        Some(Stage::Fragment) => "FRAGMENT",
+        Some(Stage::Compute) => "COMPUTE",
+        None => return Err("no entry point".to_owned()),
+    };
+
+    writeln!(out, "/// Interface of `{}`.", name).unwrap();
+    writeln!(out, "pub mod {} {{", module_name(name)).unwrap();
+    writeln!(out, "    #![allow(dead_code)]").unwrap();
+    writeln!(out, "    use super::*;").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "    pub const INTERFACE: ShaderInterface = ShaderInterface {{").unwrap();
+    writeln!(out, "        name: {:?},", name).unwrap();
+    writeln!(out, "        stage: ShaderStageFlags::{},", stage).unwrap();
+    for (field, variables) in &[("inputs", &module.inputs), ("outputs", &module.outputs)] {
+        writeln!(out, "        {}: &[", field).unwrap();
+        for variable in variables.iter() {
+            writeln!(
+                out,
+                "            Attribute {{ name: {:?}, location: {}, format: {} }},",
+                variable.name,
+                variable.location,
+                format(&variable.ty).map_or("None".to_owned(), |f| format!("Some(Format::{})", f)),
+            )
+            .unwrap();
+        }
+        writeln!(out, "        ],").unwrap();
+    }
+    writeln!(out, "        descriptor_bindings: &[").unwrap();
+    for descriptor in &module.descriptors {
+        writeln!(
+            out,
+            "            DescriptorBinding {{ name: {:?}, set: {}, binding: {}, ty: DescriptorType::{}, count: {} }},",
+            descriptor.name,
+            descriptor.set,
+            descriptor.binding,
+            descriptor_type(descriptor.kind),
+            descriptor.count,
+        )
+        .unwrap();
+    }
+    writeln!(out, "        ],").unwrap();
+    match &module.push_constants {
+        Some(block) => writeln!(out, "        push_constants: Some(0..{}),", block.size()).unwrap(),
+        None => writeln!(out, "        push_constants: None,").unwrap(),
+    }
+    writeln!(out, "    }};").unwrap();
+
+    for (module_name, variables) in &[("inputs", &module.inputs), ("outputs", &module.outputs)] {
+        writeln!(out).unwrap();
+        writeln!(out, "    /// Locations of the stage {}.", module_name).unwrap();
+        writeln!(out, "    pub mod {} {{", module_name).unwrap();
+        for variable in variables.iter() {
+            writeln!(
+                out,
+                "        pub const {}: u32 = {};",
+                constant_name(&variable.name),
+                variable.location
+            )
+            .unwrap();
+        }
+        writeln!(out, "    }}").unwrap();
+    }
+
+    writeln!(out).unwrap();
+    writeln!(out, "    /// `(set, binding)` of each resource.").unwrap();
+    writeln!(out, "    pub mod bindings {{").unwrap();
+    for descriptor in &module.descriptors {
+        writeln!(
+            out,
+            "        pub const {}: (u32, u32) = ({}, {});",
+            constant_name(&descriptor.name),
+            descriptor.set,
+            descriptor.binding
+        )
+        .unwrap();
+    }
+    writeln!(out, "    }}").unwrap();
+
+    writeln!(out).unwrap();
+    writeln!(out, "    /// `constant_id` of each specialization constant.").unwrap();
+    writeln!(out, "    pub mod spec_constants {{").unwrap();
+    for constant in &module.spec_constants {
+        writeln!(
+            out,
+            "        /// `{} {}`, {} by default.",
+            constant.ty,
+            constant.name,
+            default_value(&constant.ty, constant.default)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        pub const {}: u32 = {};",
+            constant_name(&constant.name),
+            constant.id
+        )
+        .unwrap();
+    }
+    writeln!(out, "    }}").unwrap();
+
+    let mut structs = Structs::default();
+    if let Some(block) = &module.push_constants {
+        let name = structs.add(block, Some("PushConstants"))?;
+        structs.mark_push_block(&name);
+    }
+    for descriptor in &module.descriptors {
+        if let Some(block) = &descriptor.block {
+            structs.add(block, None)?;
+        }
+    }
+    out.push_str(&structs.source);
+
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    Ok(())
+}
+
+/// `#[repr(C)]` mirrors of the blocks, padded out to the offsets the compiler chose.
+#[derive(Default)]
+struct Structs {
+    /// The Rust name of each struct added so far and the block it was made from.
+    added: Vec<(String, Type)>,
+    without_default: Vec<String>,
+    source: String,
+}
+
+impl Structs {
+    /// Adds the struct and every struct it contains, returns the Rust name. A block that's
+    /// already there is only added once, a different one with the same Rust name is an error.
+    fn add(&mut self, ty: &Type, rename: Option<&str>) -> Result<String, String> {
+        let (name, members) = match ty {
+            Type::Struct { name, members } => (name, members),
+            _ => return Err(format!("`{}` isn't a block", ty)),
+        };
+        let rust_name = rename.map_or_else(|| camel_case(name), str::to_owned);
+        if let Some((_, added)) = self.added.iter().find(|(added, _)| *added == rust_name) {
+            if added == ty {
+                return Ok(rust_name);
+            }
+            return Err(format!(
+                "`{}` and `{}` would both be `{}`, but their layouts differ",
+                added, ty, rust_name
+            ));
+        }
+        self.added.push((rust_name.clone(), ty.clone()));
+
+        let mut fields = String::new();
+        let mut offsets = String::new();
+        let mut derive_default = true;
+        let mut position = 0;
+        for (index, member) in members.iter().enumerate() {
+            if member.offset < position {
+                return Err(format!("member `{}` overlaps the previous one", member.name));
+            }
+            if member.offset > position {
+                let padding = member.offset - position;
+                derive_default &= padding <= 32;
+                writeln!(fields, "        pub _pad{}: [u8; {}],", index, padding).unwrap();
+            }
+            let rust_type = self.rust_type(&member.ty, &mut derive_default)?;
+            let field = field_name(&member.name);
+            writeln!(fields, "        pub {}: {},", field, rust_type).unwrap();
+            writeln!(
+                offsets,
+                "        pub const {}_OFFSET: u32 = {};",
+                constant_name(&member.name),
+                member.offset
+            )
+            .unwrap();
+            position = member.offset + member.ty.size();
+        }
+
+        let size = ty.size();
+        if !derive_default {
+            self.without_default.push(rust_name.clone());
+        }
+        let derives = if derive_default {
+            "Debug, Clone, Copy, Default, PartialEq"
+        } else {
+            "Debug, Clone, Copy, PartialEq"
+        };
+        let source = &mut self.source;
+        writeln!(source).unwrap();
+        writeln!(source, "    /// Layout of the `{}` block, {} bytes.", name, size).unwrap();
+        writeln!(source, "    #[repr(C)]").unwrap();
+        writeln!(source, "    #[derive({})]", derives).unwrap();
+        writeln!(source, "    pub struct {} {{", rust_name).unwrap();
+        source.push_str(&fields);
+        writeln!(source, "    }}").unwrap();
+        writeln!(source).unwrap();
+        writeln!(source, "    impl {} {{", rust_name).unwrap();
+        source.push_str(&offsets);
+        writeln!(source, "    }}").unwrap();
+        writeln!(source).unwrap();
+        writeln!(
+            source,
+            "    const _{}_SIZE: [(); {}] = [(); std::mem::size_of::<{}>()];",
+            constant_name(&rust_name),
+            size,
+            rust_name
+        )
+        .unwrap();
+        Ok(rust_name)
+    }
+
+    /// Lets `as_words` view the struct, checking at compile time that it can.
+    fn mark_push_block(&mut self, rust_name: &str) {
+        let source = &mut self.source;
+        writeln!(source).unwrap();
+        writeln!(source, "    unsafe impl PushBlock for {} {{}}", rust_name).unwrap();
+        writeln!(
+            source,
+            "    const _{}_WORDS: [(); 0] = [(); std::mem::size_of::<{}>() % 4 + std::mem::align_of::<{}>() % 4];",
+            constant_name(rust_name),
+            rust_name,
+            rust_name
+        )
+        .unwrap();
+    }
+
+    fn rust_type(&mut self, ty: &Type, derive_default: &mut bool) -> Result<String, String> {
+        Ok(match ty {
+            Type::Bool => "u32".to_owned(),
+            Type::Int { width: 32, signed: true } => "i32".to_owned(),
+            Type::Int { width: 32, signed: false } => "u32".to_owned(),
+            Type::Float { width: 32 } => "f32".to_owned(),
+            Type::Float { width: 64 } => "f64".to_owned(),
+            Type::Vector { component, count } => array(scalar(component)?, *count, derive_default),
+            Type::Matrix { column, columns, stride } => {
+                let (component, rows) = match &**column {
+                    Type::Vector { component, count } => (component, *count),
+                    _ => return Err(format!("unsupported matrix `{}`", ty)),
+                };
+                // Columns get padded up to the matrix stride, like mat3 in std140
+                let padded_rows = stride / component.size();
+                if padded_rows < rows || stride % component.size() != 0 {
+                    return Err(format!("unsupported matrix stride {} for `{}`", stride, ty));
+                }
+                let column = array(scalar(component)?, padded_rows, derive_default);
+                array(column, *columns, derive_default)
+            }
+            Type::Array { element, length: Some(length), stride } => {
+                let element_type = match &**element {
+                    _ if element.size() == *stride => self.rust_type(element, derive_default)?,
+                    // Padded scalar or vector elements, like float[4] in std140
+                    Type::Float { .. } | Type::Int { .. } | Type::Bool | Type::Vector { .. }
+                        if stride % 4 == 0 =>
+                    {
+                        let component = match &**element {
+                            Type::Vector { component, .. } => scalar(component)?,
+                            scalar_type => scalar(scalar_type)?,
+                        };
+                        array(component, stride / 4, derive_default)
+                    }
+                    _ => return Err(format!("unsupported array stride {} for `{}`", stride, ty)),
+                };
+                array(element_type, *length, derive_default)
+            }
+            Type::Struct { .. } => {
+                let name = self.add(ty, None)?;
+                *derive_default &= !self.without_default.contains(&name);
+                name
+            }
+            _ => return Err(format!("`{}` can't be used in a block", ty)),
+        })
+    }
+}
+
+/// `Default` is only derived for arrays up to 32 elements.
+fn array(element: String, length: u32, derive_default: &mut bool) -> String {
+    *derive_default &= length <= 32;
+    format!("[{}; {}]", element, length)
+}
+
+fn scalar(ty: &Type) -> Result<String, String> {
+    match ty {
+        Type::Bool => Ok("u32".to_owned()),
+        Type::Int { width: 32, signed: true } => Ok("i32".to_owned()),
+        Type::Int { width: 32, signed: false } => Ok("u32".to_owned()),
+        Type::Float { width: 32 } => Ok("f32".to_owned()),
+        Type::Float { width: 64 } => Ok("f64".to_owned()),
+        _ => Err(format!("unsupported scalar `{}`", ty)),
+    }
+}
+
+/// The name of the `Format` of a vertex attribute, only scalars and vectors have one.
+pub fn format(ty: &Type) -> Option<String> {
+    let (component, count) = match ty {
+        Type::Vector { component, count } => (&**component, *count),
+        scalar => (scalar, 1),
+    };
+    let channels = ["R", "Rg", "Rgb", "Rgba"].get(count as usize - 1)?;
+    let kind = match component {
+        Type::Float { width: 32 } => "32Sfloat",
+        Type::Float { width: 64 } => "64Sfloat",
+        Type::Int { width: 32, signed: true } => "32Sint",
+        Type::Int { width: 32, signed: false } => "32Uint",
+        _ => return None,
+    };
+    Some(format!("{}{}", channels, kind))
+}
+
+/// The default of a specialization constant as GLSL would write it.
+fn default_value(ty: &Type, bits: u32) -> String {
+    match ty {
+        Type::Bool => (bits != 0).to_string(),
+        Type::Int { signed: true, .. } => (bits as i32).to_string(),
+        Type::Float { width: 32 } => format!("{:?}", f32::from_bits(bits)),
+        _ => bits.to_string(),
+    }
+}
+
+fn descriptor_type(kind: DescriptorKind) -> &'static str {
+    match kind {
+        DescriptorKind::Sampler => "Sampler",
+        DescriptorKind::CombinedImageSampler => "CombinedImageSampler",
+        DescriptorKind::SampledImage => "SampledImage",
+        DescriptorKind::StorageImage => "StorageImage",
+        DescriptorKind::UniformTexelBuffer => "UniformTexelBuffer",
+        DescriptorKind::StorageTexelBuffer => "StorageTexelBuffer",
+        DescriptorKind::UniformBuffer => "UniformBuffer",
+        DescriptorKind::StorageBuffer => "StorageBuffer",
+        DescriptorKind::InputAttachment => "InputAttachment",
+    }
+}
+
+/// `fragCoord` becomes `frag_coord`, anything that isn't alphanumeric becomes `_`.
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            out.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        out.push(if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' });
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+fn camel_case(name: &str) -> String {
+    let out: String = snake_case(name)
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| word[..1].to_ascii_uppercase() + &word[1..])
+        .collect();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", out)
+    } else {
+        out
+    }
+}
+
+fn constant_name(name: &str) -> String {
+    snake_case(name).to_ascii_uppercase()
+}
+
+fn field_name(name: &str) -> String {
+    let name = snake_case(name);
+    match name.as_ref() {
+        "as" | "break" | "const" | "continue" | "crate" | "else" | "enum" | "extern" | "false"
+        | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move"
+        | "mut" | "pub" | "ref" | "return" | "static" | "struct" | "super" | "trait" | "true"
+        | "type" | "unsafe" | "use" | "where" | "while" | "dyn" | "async" | "await" | "try" => {
+            format!("r#{}", name)
+        }
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::reflect::Member;
+
+    fn float() -> Type {
+        Type::Float { width: 32 }
+    }
+
+    fn vec(count: u32) -> Type {
+        Type::Vector {
+            component: Box::new(float()),
+            count,
+        }
+    }
+
+    fn mat(columns: u32, rows: u32, stride: u32) -> Type {
+        Type::Matrix {
+            column: Box::new(vec(rows)),
+            columns,
+            stride,
+        }
+    }
+
+    fn member(name: &str, offset: u32, ty: Type) -> Member {
+        Member {
+            name: name.to_owned(),
+            offset,
+            ty,
+        }
+    }
+
+    #[test]
+    fn attribute_formats() {
+        assert_eq!(format(&float()), Some("R32Sfloat".to_owned()));
+        assert_eq!(format(&vec(2)), Some("Rg32Sfloat".to_owned()));
+        assert_eq!(format(&vec(3)), Some("Rgb32Sfloat".to_owned()));
+        assert_eq!(format(&vec(4)), Some("Rgba32Sfloat".to_owned()));
+        let ivec4 = Type::Vector {
+            component: Box::new(Type::Int {
+                width: 32,
+                signed: true,
+            }),
+            count: 4,
+        };
+        assert_eq!(format(&ivec4), Some("Rgba32Sint".to_owned()));
+        // Matrices take one location per column, there's no single format for them
+        assert_eq!(format(&mat(4, 4, 16)), None);
+        assert_eq!(format(&Type::Bool), None);
+    }
+
+    #[test]
+    fn push_constants_mirror_vectors_and_matrices() {
+        let block = Type::Struct {
+            name: "Inputs".to_owned(),
+            members: vec![
+                member("color", 0, vec(3)),
+                member("transform", 16, mat(4, 4, 16)),
+                // Columns of a mat3 are padded to 16 bytes
+                member("normalMatrix", 80, mat(3, 3, 16)),
+                member("offset", 128, vec(2)),
+            ],
+        };
+        let module = Module {
+            stage: Some(Stage::Fragment),
+            push_constants: Some(block),
+            ..Module::default()
+        };
+        let source = generate(&[("test.frag".to_owned(), module)]).unwrap();
+        let expected = "
+    /// Layout of the `Inputs` block, 136 bytes.
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    pub struct PushConstants {
+        pub color: [f32; 3],
+        pub _pad1: [u8; 4],
+        pub transform: [[f32; 4]; 4],
+        pub normal_matrix: [[f32; 4]; 3],
+        pub offset: [f32; 2],
+    }
+
+    impl PushConstants {
+        pub const COLOR_OFFSET: u32 = 0;
+        pub const TRANSFORM_OFFSET: u32 = 16;
+        pub const NORMAL_MATRIX_OFFSET: u32 = 80;
+        pub const OFFSET_OFFSET: u32 = 128;
+    }
+
+    const _PUSH_CONSTANTS_SIZE: [(); 136] = [(); std::mem::size_of::<PushConstants>()];
+";
+        assert!(source.contains(expected), "{}", source);
+        assert!(source.contains("        push_constants: Some(0..136),\n"));
+        assert!(source.contains("    unsafe impl PushBlock for PushConstants {}\n"));
+    }
+
+    #[test]
+    fn blocks_only_share_a_name_when_they_match() {
+        let lights = |name: &str, count: u32| {
+            let colors = Type::Array {
+                element: Box::new(vec(4)),
+                length: Some(count),
+                stride: 16,
+            };
+            Type::Struct {
+                name: name.to_owned(),
+                members: vec![member("colors", 0, colors)],
+            }
+        };
+        let mut structs = Structs::default();
+        assert_eq!(structs.add(&lights("Lights", 4), None).unwrap(), "Lights");
+        assert_eq!(structs.add(&lights("Lights", 4), None).unwrap(), "Lights");
+        assert_eq!(structs.source.matches("pub struct Lights").count(), 1);
+
+        let e = structs.add(&lights("lights", 8), None).unwrap_err();
+        assert_eq!(
+            e,
+            "`struct Lights` and `struct lights` would both be `Lights`, but their layouts differ"
+        );
+    }
+
+    #[test]
+    fn matrices_need_a_stride_that_fits_their_columns() {
+        let block = Type::Struct {
+            name: "Inputs".to_owned(),
+            members: vec![member("transform", 0, mat(4, 4, 8))],
+        };
+        let module = Module {
+            stage: Some(Stage::Vertex),
+            push_constants: Some(block),
+            ..Module::default()
+        };
+        let e = generate(&[("test.vert".to_owned(), module)]).unwrap_err();
+        assert!(e.contains("unsupported matrix stride 8"), "{}", e);
+    }
+}
@@ -0,0 +1,219 @@
+//! Remembers what every `.spv` in the output directory was compiled from, so unchanged shaders
+//! skip `glslangValidator` and outputs of deleted shaders get cleaned up.
+//!
+//! The key of a shader is a hash of its preprocessed source, which already contains every
+//! include and injected define, along with its stage and the compiler version.
+
+use crate::diagnostics::Diagnostic;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST: &str = "manifest.txt";
+
+/// Bump when the compiler or the way sources are preprocessed changes.
+const CACHE_VERSION: &str = "nicegfx-shader-cache 1, glsl-to-spirv 0.1.6";
+
+/// The output directory along with what it held before this build and what it holds now.
+pub struct Cache {
+    dir: PathBuf,
+    previous: Manifest,
+    current: Manifest,
+}
+
+impl Cache {
+    pub fn open(dir: &Path) -> Self {
+        Cache {
+            dir: dir.to_owned(),
+            previous: Manifest::load(dir),
+            current: Manifest::default(),
+        }
+    }
+
+    /// The SPIR-V of `name`, from the previous build when `key` matches or from `compile`.
+    pub fn get_or_compile<F>(
+        &mut self,
+        name: &str,
+        key: u64,
+        compile: F,
+    ) -> Result<Vec<u8>, Vec<Diagnostic>>
+    where
+        F: FnOnce() -> Result<Vec<u8>, Vec<Diagnostic>>,
+    {
+        let compiled_bytes = match self.previous.cached(&self.dir, name, key) {
+            Some(compiled_bytes) => compiled_bytes,
+            None => {
+                let compiled_bytes = compile()?;
+                let out_path = self.dir.join(format!("{}.spv", name));
+                std::fs::write(&out_path, &compiled_bytes).map_err(|e| {
+                    let message = format!("couldn't write {}: {}", out_path.display(), e);
+                    vec![Diagnostic::error(message)]
+                })?;
+                compiled_bytes
+            }
+        };
+        self.current.insert(name.to_owned(), key);
+        Ok(compiled_bytes)
+    }
+
+    /// Saves the manifest and deletes the outputs of shaders that are gone.
+    pub fn finish(self) -> std::io::Result<()> {
+        self.current.save(&self.dir)?;
+        self.current.remove_stale(&self.dir)
+    }
+}
+
+/// Shader name to the key of the source its `.spv` was compiled from.
+#[derive(Debug, Default)]
+struct Manifest {
+    entries: HashMap<String, u64>,
+}
+
+impl Manifest {
+    /// A missing or unreadable manifest is an empty one, everything just gets compiled.
+    fn load(spirv_dir: &Path) -> Self {
+        let entries = std::fs::read_to_string(spirv_dir.join(MANIFEST))
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|line| {
+                        let mut words = line.splitn(2, ' ');
+                        let key = u64::from_str_radix(words.next()?, 16).ok()?;
+                        Some((words.next()?.to_owned(), key))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Manifest { entries }
+    }
+
+    fn save(&self, spirv_dir: &Path) -> std::io::Result<()> {
+        let mut lines: Vec<String> = self
+            .entries
+            .iter()
+            .map(|(name, key)| format!("{:016x} {}", key, name))
+            .collect();
+        lines.sort();
+        lines.push(String::new());
+        std::fs::write(spirv_dir.join(MANIFEST), lines.join("\n"))
+    }
+
+    /// The compiled module from a previous build, if its source hasn't changed since.
+    fn cached(&self, spirv_dir: &Path, name: &str, key: u64) -> Option<Vec<u8>> {
+        if self.entries.get(name) != Some(&key) {
+            return None;
+        }
+        std::fs::read(spirv_dir.join(format!("{}.spv", name))).ok()
+    }
+
+    fn insert(&mut self, name: String, key: u64) {
+        self.entries.insert(name, key);
+    }
+
+    /// Deletes the `.spv` files that aren't in the manifest anymore.
+    fn remove_stale(&self, spirv_dir: &Path) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(spirv_dir)? {
+            let path = entry?.path();
+            if path.extension() != Some("spv".as_ref()) {
+                continue;
+            }
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            if !self.entries.contains_key(&name) {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The cache key of a preprocessed shader source.
+pub fn key(stage: &str, source: &str) -> u64 {
+    // FNV-1a, stable across builds unlike `DefaultHasher`
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for bytes in &[
+        CACHE_VERSION.as_bytes(),
+        b"\0",
+        stage.as_bytes(),
+        b"\0",
+        source.as_bytes(),
+    ] {
+        for byte in bytes.iter() {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocess;
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("nicegfx-cache-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn includes_are_part_of_the_key() {
+        let root = temp_dir("key");
+        let main = root.join("main.frag");
+        std::fs::write(
+            &main,
+            "#version 450\n#include \"common.glsl\"\nvoid main() {}\n",
+        )
+        .unwrap();
+        let key_with = |common: &str| {
+            std::fs::write(root.join("common.glsl"), common).unwrap();
+            let preprocessed = preprocess::preprocess(&root, &main, &[]).unwrap();
+            key("Fragment", &preprocessed.source)
+        };
+
+        let before = key_with("const float GAMMA = 2.2;\n");
+        assert_eq!(key_with("const float GAMMA = 2.2;\n"), before);
+        assert_ne!(key_with("const float GAMMA = 2.4;\n"), before);
+
+        let preprocessed = preprocess::preprocess(&root, &main, &[]).unwrap();
+        assert_ne!(
+            key("Vertex", &preprocessed.source),
+            key("Fragment", &preprocessed.source)
+        );
+        let defines = vec![("FOG".to_owned(), None)];
+        let with_fog = preprocess::preprocess(&root, &main, &defines).unwrap();
+        assert_ne!(
+            key("Fragment", &with_fog.source),
+            key("Fragment", &preprocessed.source)
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn finish_removes_stale_outputs() {
+        let dir = temp_dir("stale");
+        std::fs::write(dir.join("gone.frag.spv"), [1, 2, 3, 4]).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a shader").unwrap();
+
+        let mut cache = Cache::open(&dir);
+        let compiled = cache.get_or_compile("kept.frag", 1, || Ok(vec![5, 6, 7, 8]));
+        assert_eq!(compiled.unwrap(), [5, 6, 7, 8]);
+        cache.finish().unwrap();
+        assert!(dir.join("kept.frag.spv").exists());
+        assert!(!dir.join("gone.frag.spv").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        // The next build picks it up without compiling, until the key changes
+        let mut cache = Cache::open(&dir);
+        let cached = cache.get_or_compile("kept.frag", 1, || panic!("compiled again"));
+        assert_eq!(cached.unwrap(), [5, 6, 7, 8]);
+        let recompiled = cache.get_or_compile("kept.frag", 2, || Ok(vec![9, 9, 9, 9]));
+        assert_eq!(recompiled.unwrap(), [9, 9, 9, 9]);
+        cache.finish().unwrap();
+        assert_eq!(Manifest::load(&dir).entries.get("kept.frag"), Some(&2));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
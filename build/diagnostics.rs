@@ -0,0 +1,155 @@
+//! Turns compiler output into messages that point at the original shader sources, in the
+//! usual `file:line:column` form with the offending line underneath.
+
+use super::preprocess::{Preprocessed, SourceError};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// `None` when the compiler didn't say where the problem is.
+    pub path: Option<PathBuf>,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub source_line: Option<String>,
+    /// The shader variant being compiled when this came up, if it isn't the plain shader.
+    pub variant: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+            path: None,
+            line: 0,
+            column: None,
+            source_line: None,
+            variant: None,
+        }
+    }
+
+    pub fn in_variant(mut self, variant: &str) -> Self {
+        self.variant = Some(variant.to_owned());
+        self
+    }
+}
+
+impl From<SourceError> for Diagnostic {
+    fn from(error: SourceError) -> Self {
+        let source_line = line_of(&error.path, error.line);
+        Diagnostic {
+            path: Some(error.path),
+            line: error.line,
+            source_line,
+            ..Diagnostic::error(error.message)
+        }
+    }
+}
+
+/// Parses the `ERROR: <file>:<line>: <message>` lines `glslangValidator` prints, mapping the
+/// lines of the preprocessed source back to the files they came from.
+pub fn parse_glslang(output: &str, preprocessed: &Preprocessed) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for line in output.lines() {
+        let (severity, rest) = if let Some(rest) = line.strip_prefix("ERROR: ") {
+            (Severity::Error, rest)
+        } else if let Some(rest) = line.strip_prefix("WARNING: ") {
+            (Severity::Warning, rest)
+        } else {
+            continue;
+        };
+        // `<file or string number>:<line>: <message>`, the file may contain `:` on Windows
+        let (location, message) = match rest.find(": ") {
+            Some(split) => (&rest[..split], rest[split + 2..].trim()),
+            None => continue,
+        };
+        let line_number = match location.rfind(':').map(|i| location[i + 1..].parse::<usize>()) {
+            Some(Ok(line_number)) => line_number,
+            // The "N compilation errors" summary and friends
+            _ => continue,
+        };
+
+        let mut diagnostic = Diagnostic {
+            severity,
+            ..Diagnostic::error(message.to_owned())
+        };
+        if let Some((path, line)) = preprocessed.origin(line_number) {
+            diagnostic.source_line = line_of(path, line);
+            diagnostic.column = diagnostic
+                .source_line
+                .as_ref()
+                .and_then(|source_line| column_of(source_line, message));
+            diagnostic.path = Some(path.to_owned());
+            diagnostic.line = line;
+        }
+        diagnostics.push(diagnostic);
+    }
+
+    if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        // Couldn't make sense of the output, show it as is
+        diagnostics.push(Diagnostic::error(output.trim().to_owned()));
+    }
+    diagnostics
+}
+
+/// glslang quotes the offending token, like `'foo' : undeclared identifier`.
+fn column_of(source_line: &str, message: &str) -> Option<usize> {
+    let start = message.find('\'')? + 1;
+    let end = start + message[start..].find('\'')?;
+    let token = message[start..end].trim();
+    if token.is_empty() {
+        return None;
+    }
+    source_line
+        .find(token)
+        .map(|byte| source_line[..byte].chars().count() + 1)
+}
+
+fn line_of(path: &Path, line: usize) -> Option<String> {
+    let source = std::fs::read_to_string(path).ok()?;
+    source
+        .lines()
+        .nth(line.checked_sub(1)?)
+        .map(|line| line.trim_end().to_owned())
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)?;
+        if let Some(variant) = &self.variant {
+            write!(f, " (in {})", variant)?;
+        }
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        match (self.line, self.column) {
+            (0, _) => write!(f, "\n  --> {}", path.display())?,
+            (line, None) => write!(f, "\n  --> {}:{}", path.display(), line)?,
+            (line, Some(column)) => write!(f, "\n  --> {}:{}:{}", path.display(), line, column)?,
+        }
+        if let Some(source_line) = &self.source_line {
+            let gutter = self.line.to_string();
+            let padding = " ".repeat(gutter.len());
+            write!(f, "\n {} |", padding)?;
+            write!(f, "\n {} | {}", gutter, source_line)?;
+            if let Some(column) = self.column {
+                write!(f, "\n {} | {}^", padding, " ".repeat(column - 1))?;
+            }
+        }
+        Ok(())
+    }
+}
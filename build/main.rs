@@ -0,0 +1,179 @@
+extern crate glsl_to_spirv;
+
+mod bindings;
+mod cache;
+mod diagnostics;
+mod preprocess;
+mod reflect;
+mod validate;
+
+use diagnostics::{Diagnostic, Severity};
+use glsl_to_spirv::ShaderType;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+const SHADER_ROOT: &str = "pre_assets/shaders";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // Run again if a shader is added or removed, the sources and their includes are listed below
+    println!("cargo:rerun-if-changed={}", SHADER_ROOT);
+
+    // Compiled shaders stay out of the source tree
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    let spirv_dir = out_dir.join("shaders");
+    std::fs::create_dir_all(&spirv_dir)?;
+
+    // Unchanged shaders are picked up from the previous build
+    let mut shader_cache = cache::Cache::open(&spirv_dir);
+
+    let root = Path::new(SHADER_ROOT);
+    let mut modules = vec![];
+    let mut sources = HashMap::new();
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_file() {
+            let in_path = entry.path();
+
+            //Vertex and fragment shaders for now, `.glsl` files are only included
+            let shader_type = in_path.extension().and_then(|ext| {
+                match ext.to_string_lossy().as_ref() {
+                    "vert" => Some(ShaderType::Vertex),
+                    "frag" => Some(ShaderType::Fragment),
+                    _ => None,
+                }
+            });
+
+            if let Some(shader_type) = shader_type {
+                println!("cargo:rerun-if-changed={}", in_path.display());
+
+                let file_name = in_path.file_name().unwrap().to_string_lossy();
+                let stem = in_path.file_stem().unwrap().to_string_lossy();
+                let extension = in_path.extension().unwrap().to_string_lossy();
+
+                // The plain shader first, then one output per permutation combination
+                let source = std::fs::read_to_string(&in_path)?;
+                let mut variants = vec![(file_name.to_string(), vec![])];
+                match preprocess::permutations(&in_path, &source) {
+                    Ok(permutations) => {
+                        for permutation in permutations {
+                            let name = format!("{}.{}.{}", stem, permutation.name, extension);
+                            variants.push((name, permutation.defines));
+                        }
+                    }
+                    Err(e) => diagnostics.push(e.into()),
+                }
+
+                for (name, defines) in variants {
+                    let compiled = preprocess::preprocess(root, &in_path, &defines)
+                        .map_err(|e| vec![e.into()])
+                        .and_then(|preprocessed| {
+                            for include in &preprocessed.includes {
+                                println!("cargo:rerun-if-changed={}", include.display());
+                            }
+                            let stage = format!("{:?}", shader_type);
+                            let key = cache::key(&stage, &preprocessed.source);
+                            let compiled_bytes = shader_cache.get_or_compile(&name, key, || {
+                                compile(&in_path, &preprocessed, shader_type.clone())
+                            })?;
+                            reflect::reflect(&compiled_bytes).map_err(|e| {
+                                let message = format!("couldn't reflect {}: {}", name, e);
+                                vec![Diagnostic::error(message)]
+                            })
+                        });
+                    match compiled {
+                        Ok(module) => {
+                            sources.insert(name.clone(), in_path.clone());
+                            modules.push((name, module));
+                        }
+                        Err(errors) => diagnostics.extend(errors.into_iter().map(|d| {
+                            if name == file_name {
+                                d
+                            } else {
+                                d.in_variant(&name)
+                            }
+                        })),
+                    }
+                }
+            }
+        }
+    }
+
+    // Vertex outputs have to match the fragment inputs they feed
+    let manifest = validate::read_manifest(root)?;
+    if let Some((path, _)) = &manifest {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    let shaders: Vec<validate::Shader> = modules
+        .iter()
+        .map(|(name, module)| validate::Shader {
+            name,
+            path: &sources[name],
+            module,
+        })
+        .collect();
+    let manifest = manifest
+        .as_ref()
+        .map(|(path, contents)| (path.as_path(), contents.as_str()));
+    match validate::pairs(&shaders, manifest) {
+        Ok(pairs) => {
+            for (vertex, fragment) in pairs {
+                let find = |name: &str| shaders.iter().find(|shader| shader.name == name).unwrap();
+                diagnostics.extend(validate::check(find(&vertex), find(&fragment)));
+            }
+        }
+        Err(diagnostic) => diagnostics.push(*diagnostic),
+    }
+
+    // Everything got compiled and checked, so every broken shader shows up at once
+    if !diagnostics.is_empty() {
+        for diagnostic in &diagnostics {
+            for line in diagnostic.to_string().lines() {
+                println!("cargo:warning={}", line);
+            }
+            eprintln!("{}\n", diagnostic);
+        }
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        if errors > 0 {
+            return Err(format!("{} shader error(s), see the messages above", errors).into());
+        }
+    }
+
+    shader_cache.finish()?;
+
+    // Rust mirror of every shader interface and the SPIR-V table, included by `src/shaders.rs`
+    modules.sort_by(|a, b| a.0.cmp(&b.0));
+    std::fs::write(out_dir.join("shader_bindings.rs"), bindings::generate(&modules)?)?;
+    let embed = std::env::var_os("CARGO_FEATURE_SHADERS_FROM_DISK").is_none();
+    let names: Vec<&str> = modules.iter().map(|(name, _)| name.as_str()).collect();
+    std::fs::write(
+        out_dir.join("shader_table.rs"),
+        bindings::spirv_table(&names, &spirv_dir, embed),
+    )?;
+
+    Ok(())
+}
+
+/// Compiles one preprocessed variant of the shader at `path`.
+fn compile(
+    path: &Path,
+    preprocessed: &preprocess::Preprocessed,
+    shader_type: ShaderType,
+) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    use std::io::Read;
+
+    let mut compiled_file = glsl_to_spirv::compile(&preprocessed.source, shader_type)
+        .map_err(|output| diagnostics::parse_glslang(&output, preprocessed))?;
+
+    let mut compiled_bytes = Vec::new();
+    compiled_file.read_to_end(&mut compiled_bytes).map_err(|e| {
+        let message = format!("couldn't read the compiled {}: {}", path.display(), e);
+        vec![Diagnostic::error(message)]
+    })?;
+    Ok(compiled_bytes)
+}
@@ -0,0 +1,467 @@
+//! A small GLSL preprocessor that runs before the source reaches `glslangValidator`.
+//!
+//! It understands three things:
+//! * `#include "file.glsl"`, resolved relative to the shader root. Every file is only
+//!   included once per shader, so shared headers don't need include guards. A file including
+//!   itself, directly or through others, is an error.
+//! * `#pragma permutation <name> NAME[=VALUE]...`, which asks for extra variants of the
+//!   shader compiled with the given `#define`s, on their own and combined with the other
+//!   permutations: `high` and `fog` make `high`, `fog` and `high.fog`.
+//! * Injected `#define`s, placed right after the `#version` line.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A `#define` name with an optional value.
+pub type Define = (String, Option<String>);
+
+/// A named set of defines, declared with `#pragma permutation`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Permutation {
+    pub name: String,
+    pub defines: Vec<Define>,
+}
+
+/// The flattened source of a shader and every file it pulled in.
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessed {
+    pub source: String,
+    pub includes: Vec<PathBuf>,
+    /// The shader itself followed by `includes`.
+    files: Vec<PathBuf>,
+    /// Index into `files` and line number of every line of `source`, `None` for lines that
+    /// aren't in any file.
+    origins: Vec<Option<(usize, usize)>>,
+}
+
+impl Preprocessed {
+    /// Sources pasted one after the other as they are, with every line still pointing back at
+    /// the file it came from. Parts without a path point nowhere.
+    pub fn concat(parts: &[(Option<&Path>, &str)]) -> Self {
+        let mut preprocessed = Preprocessed::default();
+        for (path, source) in parts {
+            let file = path.map(|path| preprocessed.file(path));
+            for (index, line) in source.lines().enumerate() {
+                preprocessed.source.push_str(line);
+                preprocessed.source.push('\n');
+                let origin = file.map(|file| (file, index + 1));
+                preprocessed.origins.push(origin);
+            }
+        }
+        preprocessed.includes = preprocessed.files.iter().skip(1).cloned().collect();
+        preprocessed
+    }
+
+    /// Index of `path` in `files`, added if it isn't there yet.
+    fn file(&mut self, path: &Path) -> usize {
+        match self.files.iter().position(|file| file == path) {
+            Some(file) => file,
+            None => {
+                self.files.push(path.to_path_buf());
+                self.files.len() - 1
+            }
+        }
+    }
+
+    /// Maps a 1-based line of `source` back to the file and line it came from.
+    pub fn origin(&self, line: usize) -> Option<(&Path, usize)> {
+        let (file, line) = (*self.origins.get(line.checked_sub(1)?)?)?;
+        Some((&self.files[file], line))
+    }
+}
+
+/// An error pointing at a line of a shader source, line 0 meaning the whole file.
+#[derive(Debug, Clone)]
+pub struct SourceError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.path.display(), self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+fn error(path: &Path, line: usize, message: String) -> SourceError {
+    SourceError {
+        path: path.to_owned(),
+        line,
+        message,
+    }
+}
+
+/// Every combination of the `#pragma permutation` lines of the top level shader at `path`,
+/// named after the permutations in it joined with `.`.
+pub fn permutations(path: &Path, source: &str) -> Result<Vec<Permutation>, SourceError> {
+    let mut permutations: Vec<Permutation> = vec![];
+    for (index, line) in source.lines().enumerate() {
+        let mut words = match permutation_directive(line) {
+            Some(rest) => rest.split_whitespace(),
+            None => continue,
+        };
+        let error = |message: String| error(path, index + 1, message);
+        let name = words
+            .next()
+            .ok_or_else(|| error("permutation without a name".to_owned()))?;
+        if !is_identifier(name) {
+            return Err(error(format!("invalid permutation name `{}`", name)));
+        }
+        if permutations.iter().any(|p| p.name == name) {
+            return Err(error(format!("duplicate permutation `{}`", name)));
+        }
+        let defines = words
+            .map(|word| parse_define(word).map_err(&error))
+            .collect::<Result<Vec<_>, _>>()?;
+        permutations.push(Permutation {
+            name: name.to_owned(),
+            defines,
+        });
+    }
+
+    let mut combinations: Vec<Permutation> = vec![];
+    for permutation in permutations {
+        let combined: Vec<Permutation> = combinations
+            .iter()
+            .map(|combination| Permutation {
+                name: format!("{}.{}", combination.name, permutation.name),
+                defines: combination
+                    .defines
+                    .iter()
+                    .chain(&permutation.defines)
+                    .cloned()
+                    .collect(),
+            })
+            .collect();
+        combinations.push(permutation);
+        combinations.extend(combined);
+    }
+    Ok(combinations)
+}
+
+/// Parses `NAME` or `NAME=VALUE`.
+pub fn parse_define(word: &str) -> Result<Define, String> {
+    let (name, value) = match word.find('=') {
+        Some(split) => (&word[..split], Some(word[split + 1..].to_owned())),
+        None => (word, None),
+    };
+    if is_identifier(name) {
+        Ok((name.to_owned(), value))
+    } else {
+        Err(format!("invalid define name `{}`", name))
+    }
+}
+
+/// Resolves the includes of the shader at `path` and injects `defines` after `#version`.
+pub fn preprocess(
+    root: &Path,
+    path: &Path,
+    defines: &[Define],
+) -> Result<Preprocessed, SourceError> {
+    let mut state = State {
+        root,
+        output: String::new(),
+        files: vec![path.to_owned()],
+        origins: vec![],
+        seen: HashSet::new(),
+        stack: vec![],
+    };
+    state.seen.insert(identity(path));
+    let source = read(path).map_err(|e| error(path, 0, e))?;
+    state.expand(0, &source, Some(defines))?;
+    Ok(Preprocessed {
+        source: state.output,
+        includes: state.files[1..].to_vec(),
+        files: state.files,
+        origins: state.origins.into_iter().map(Some).collect(),
+    })
+}
+
+struct State<'a> {
+    root: &'a Path,
+    output: String,
+    files: Vec<PathBuf>,
+    origins: Vec<(usize, usize)>,
+    seen: HashSet<PathBuf>,
+    /// Indices into `files` of the file being expanded and the ones including it.
+    stack: Vec<usize>,
+}
+
+impl<'a> State<'a> {
+    /// `defines` is `Some` only for the top level file, where they get injected.
+    fn expand(
+        &mut self,
+        file: usize,
+        source: &str,
+        defines: Option<&[Define]>,
+    ) -> Result<(), SourceError> {
+        let path = self.files[file].clone();
+        self.stack.push(file);
+        let mut pending_defines = defines;
+        for (index, line) in source.lines().enumerate() {
+            let trimmed = line.trim_start();
+            let origin = (file, index + 1);
+            let error = |message: String| error(&path, index + 1, message);
+
+            if trimmed.starts_with("#version") {
+                if defines.is_none() {
+                    return Err(error("included files can't declare #version".to_owned()));
+                }
+                self.push_line(line, origin);
+                if let Some(defines) = pending_defines.take() {
+                    self.push_defines(defines, origin);
+                }
+            } else if let Some(rest) = directive(trimmed, "include") {
+                let name = parse_include(rest).ok_or_else(|| {
+                    error(format!("expected #include \"file\", found `{}`", trimmed))
+                })?;
+                let include_path = self.root.join(name);
+                self.check_cycle(&include_path).map_err(error)?;
+                if self.seen.insert(identity(&include_path)) {
+                    let include_source = read(&include_path).map_err(error)?;
+                    self.files.push(include_path);
+                    self.expand(self.files.len() - 1, &include_source, None)?;
+                }
+                // Keeps the line count of the including file intact
+                self.push_line("", origin);
+            } else if permutation_directive(trimmed).is_some() {
+                self.push_line("", origin);
+            } else {
+                self.push_line(line, origin);
+            }
+        }
+
+        // No #version line, so the defines go first
+        if let Some(defines) = pending_defines {
+            let body = std::mem::take(&mut self.output);
+            let origins = std::mem::take(&mut self.origins);
+            self.push_defines(defines, (file, 1));
+            self.output.push_str(&body);
+            self.origins.extend(origins);
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Fails if `include_path` is one of the files including it.
+    fn check_cycle(&self, include_path: &Path) -> Result<(), String> {
+        let included = identity(include_path);
+        let start = match self
+            .stack
+            .iter()
+            .position(|&file| identity(&self.files[file]) == included)
+        {
+            Some(start) => start,
+            None => return Ok(()),
+        };
+        let cycle: Vec<String> = self.stack[start..]
+            .iter()
+            .map(|&file| self.files[file].display().to_string())
+            .chain(Some(include_path.display().to_string()))
+            .collect();
+        Err(format!("#include cycle: {}", cycle.join(" -> ")))
+    }
+
+    fn push_line(&mut self, line: &str, origin: (usize, usize)) {
+        self.output.push_str(line);
+        self.output.push('\n');
+        self.origins.push(origin);
+    }
+
+    fn push_defines(&mut self, defines: &[Define], origin: (usize, usize)) {
+        for (name, value) in defines {
+            match value {
+                Some(value) => self.push_line(&format!("#define {} {}", name, value), origin),
+                None => self.push_line(&format!("#define {}", name), origin),
+            }
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))
+}
+
+/// Canonical path when the file exists, so `a/../b.glsl` and `b.glsl` count as the same file.
+fn identity(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+/// Returns what follows `#<name>` (the preprocessor allows spaces after the `#`).
+fn directive<'s>(line: &'s str, name: &str) -> Option<&'s str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix(name)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) || rest.starts_with('"') {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+fn permutation_directive(line: &str) -> Option<&str> {
+    let rest = directive(line, "pragma")?;
+    rest.strip_prefix("permutation").map(str::trim)
+}
+
+fn parse_include(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    if rest.len() >= 2 && rest.starts_with('"') && rest.ends_with('"') {
+        Some(&rest[1..rest.len() - 1]).filter(|name| !name.is_empty())
+    } else {
+        None
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh shader root holding `files`.
+    fn shader_root(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "nicegfx-preprocess-{}-{}",
+            std::process::id(),
+            test
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        for (name, source) in files {
+            std::fs::write(root.join(name), source).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn nested_includes_are_pasted_once() {
+        let root = shader_root(
+            "nested",
+            &[
+                (
+                    "main.frag",
+                    "#version 450\n#include \"a.glsl\"\n#include \"b.glsl\"\nvoid main() {}\n",
+                ),
+                ("a.glsl", "#include \"b.glsl\"\nfloat a() { return b(); }\n"),
+                ("b.glsl", "float b() { return 1.0; }\n"),
+            ],
+        );
+        let main = root.join("main.frag");
+        let defines = vec![("FOG".to_owned(), Some("2".to_owned()))];
+        let preprocessed = preprocess(&root, &main, &defines).unwrap();
+
+        let lines: Vec<&str> = preprocessed.source.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "#version 450",
+                "#define FOG 2",
+                "float b() { return 1.0; }",
+                "",
+                "float a() { return b(); }",
+                "",
+                "",
+                "void main() {}",
+            ]
+        );
+        assert_eq!(
+            preprocessed.includes,
+            [root.join("a.glsl"), root.join("b.glsl")]
+        );
+        let b = root.join("b.glsl");
+        assert_eq!(preprocessed.origin(3), Some((b.as_path(), 1)));
+        let a = root.join("a.glsl");
+        assert_eq!(preprocessed.origin(5), Some((a.as_path(), 2)));
+        assert_eq!(preprocessed.origin(8), Some((main.as_path(), 4)));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn cyclic_includes_are_an_error() {
+        let root = shader_root(
+            "cycle",
+            &[
+                ("main.frag", "#version 450\n#include \"a.glsl\"\n"),
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "\n#include \"a.glsl\"\n"),
+            ],
+        );
+        let e = preprocess(&root, &root.join("main.frag"), &[]).unwrap_err();
+        assert_eq!(e.path, root.join("b.glsl"));
+        assert_eq!(e.line, 2);
+        assert!(e.message.starts_with("#include cycle: "), "{}", e.message);
+        assert!(e.message.ends_with("a.glsl"), "{}", e.message);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn missing_includes_are_an_error() {
+        let root = shader_root(
+            "missing",
+            &[("main.frag", "#version 450\n\n#include \"missing.glsl\"\n")],
+        );
+        let e = preprocess(&root, &root.join("main.frag"), &[]).unwrap_err();
+        assert_eq!(e.path, root.join("main.frag"));
+        assert_eq!(e.line, 3);
+        assert!(e.message.contains("missing.glsl"), "{}", e.message);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn permutations_combine_with_each_other() {
+        let source = "#version 450\n\
+                      #pragma permutation high QUALITY=2\n\
+                      #pragma permutation fog FOG\n\
+                      #pragma permutation shadows SHADOWS CASCADES=4\n";
+        let permutations = permutations(Path::new("main.frag"), source).unwrap();
+        let names: Vec<&str> = permutations.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "high",
+                "fog",
+                "high.fog",
+                "shadows",
+                "high.shadows",
+                "fog.shadows",
+                "high.fog.shadows",
+            ]
+        );
+
+        let define = |name: &str, value: Option<&str>| (name.to_owned(), value.map(str::to_owned));
+        assert_eq!(
+            permutations[4].defines,
+            [
+                define("QUALITY", Some("2")),
+                define("SHADOWS", None),
+                define("CASCADES", Some("4")),
+            ]
+        );
+        assert_eq!(permutations[6].defines.len(), 4);
+    }
+
+    #[test]
+    fn duplicate_permutations_are_an_error() {
+        let source = "#pragma permutation fog FOG\n#pragma permutation fog FOG=2\n";
+        let e = permutations(Path::new("main.frag"), source).unwrap_err();
+        assert_eq!(e.line, 2);
+    }
+}
@@ -0,0 +1,813 @@
+//! Just enough of a SPIR-V parser to pull out the interface of a shader module: the stage
+//! inputs and outputs, the descriptor bindings, the push constant block and the
+//! specialization constants.
+
+use std::collections::HashMap;
+use std::fmt;
+
+const MAGIC: u32 = 0x0723_0203;
+
+// Opcodes
+const OP_NAME: u16 = 5;
+const OP_MEMBER_NAME: u16 = 6;
+const OP_ENTRY_POINT: u16 = 15;
+const OP_TYPE_BOOL: u16 = 20;
+const OP_TYPE_INT: u16 = 21;
+const OP_TYPE_FLOAT: u16 = 22;
+const OP_TYPE_VECTOR: u16 = 23;
+const OP_TYPE_MATRIX: u16 = 24;
+const OP_TYPE_IMAGE: u16 = 25;
+const OP_TYPE_SAMPLER: u16 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u16 = 27;
+const OP_TYPE_ARRAY: u16 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u16 = 29;
+const OP_TYPE_STRUCT: u16 = 30;
+const OP_TYPE_POINTER: u16 = 32;
+const OP_CONSTANT: u16 = 43;
+const OP_SPEC_CONSTANT_TRUE: u16 = 48;
+const OP_SPEC_CONSTANT_FALSE: u16 = 49;
+const OP_SPEC_CONSTANT: u16 = 50;
+const OP_VARIABLE: u16 = 59;
+const OP_DECORATE: u16 = 71;
+const OP_MEMBER_DECORATE: u16 = 72;
+
+// Decorations
+const SPEC_ID: u32 = 1;
+const BLOCK: u32 = 2;
+const BUFFER_BLOCK: u32 = 3;
+const ARRAY_STRIDE: u32 = 6;
+const MATRIX_STRIDE: u32 = 7;
+const BUILT_IN: u32 = 11;
+const LOCATION: u32 = 30;
+const BINDING: u32 = 33;
+const DESCRIPTOR_SET: u32 = 34;
+const OFFSET: u32 = 35;
+
+// Storage classes
+const UNIFORM_CONSTANT: u32 = 0;
+const INPUT: u32 = 1;
+const UNIFORM: u32 = 2;
+const OUTPUT: u32 = 3;
+const PUSH_CONSTANT: u32 = 9;
+const STORAGE_BUFFER: u32 = 12;
+
+// Image dimensions
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Vertex,
+    Hull,
+    Domain,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: Box<Type>, count: u32 },
+    Matrix { column: Box<Type>, columns: u32, stride: u32 },
+    Array { element: Box<Type>, length: Option<u32>, stride: u32 },
+    Struct { name: String, members: Vec<Member> },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Unsupported,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub offset: u32,
+    pub ty: Type,
+}
+
+/// An `in` or `out` variable with an explicit location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub location: u32,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorKind {
+    Sampler,
+    CombinedImageSampler,
+    SampledImage,
+    StorageImage,
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    UniformBuffer,
+    StorageBuffer,
+    InputAttachment,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Descriptor {
+    pub name: String,
+    pub set: u32,
+    pub binding: u32,
+    pub kind: DescriptorKind,
+    pub count: u32,
+    /// The block layout of uniform and storage buffers.
+    pub block: Option<Type>,
+}
+
+/// A `layout(constant_id = N) const` declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecConstant {
+    pub name: String,
+    pub id: u32,
+    pub ty: Type,
+    /// The low word of the default value, `1` or `0` for booleans.
+    pub default: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub stage: Option<Stage>,
+    pub inputs: Vec<Variable>,
+    pub outputs: Vec<Variable>,
+    pub descriptors: Vec<Descriptor>,
+    /// Always a `Type::Struct`.
+    pub push_constants: Option<Type>,
+    pub spec_constants: Vec<SpecConstant>,
+}
+
+impl Type {
+    /// Size in bytes, following the strides the compiler decorated the types with.
+    pub fn size(&self) -> u32 {
+        match self {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => width / 8,
+            Type::Vector { component, count } => component.size() * count,
+            Type::Matrix { columns, stride, .. } => columns * stride,
+            Type::Array { length, stride, .. } => length.unwrap_or(0) * stride,
+            Type::Struct { members, .. } => members
+                .iter()
+                .map(|member| member.offset + member.ty.size())
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn prefix(component: &Type) -> &'static str {
+            match component {
+                Type::Bool => "b",
+                Type::Int { signed: true, .. } => "i",
+                Type::Int { signed: false, .. } => "u",
+                Type::Float { width: 64 } => "d",
+                _ => "",
+            }
+        }
+        match self {
+            Type::Bool => write!(f, "bool"),
+            Type::Int { signed: true, .. } => write!(f, "int"),
+            Type::Int { signed: false, .. } => write!(f, "uint"),
+            Type::Float { width: 64 } => write!(f, "double"),
+            Type::Float { .. } => write!(f, "float"),
+            Type::Vector { component, count } => write!(f, "{}vec{}", prefix(component), count),
+            Type::Matrix { column, columns, .. } => match **column {
+                Type::Vector { ref component, count } if count == *columns => {
+                    write!(f, "{}mat{}", prefix(component), columns)
+                }
+                Type::Vector { ref component, count } => {
+                    write!(f, "{}mat{}x{}", prefix(component), columns, count)
+                }
+                _ => write!(f, "mat"),
+            },
+            Type::Array { element, length: Some(length), .. } => write!(f, "{}[{}]", element, length),
+            Type::Array { element, length: None, .. } => write!(f, "{}[]", element),
+            Type::Struct { name, .. } => write!(f, "struct {}", name),
+            Type::Image { .. } => write!(f, "image"),
+            Type::Sampler => write!(f, "sampler"),
+            Type::SampledImage => write!(f, "sampled image"),
+            Type::Unsupported => write!(f, "<unsupported>"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum RawType {
+    Bool,
+    Int(u32, bool),
+    Float(u32),
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Image(u32, u32),
+    Sampler,
+    SampledImage,
+    Array(u32, Option<u32>),
+    Struct(Vec<u32>),
+    Pointer(u32),
+}
+
+/// `(decoration, literals)` pairs.
+type Decorations = Vec<(u32, Vec<u32>)>;
+
+#[derive(Default)]
+struct Parser {
+    stage: Option<Stage>,
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), Decorations>,
+    types: HashMap<u32, RawType>,
+    constants: HashMap<u32, u32>,
+    // (pointer type, id, storage class)
+    variables: Vec<(u32, u32, u32)>,
+    // (result type, id, default)
+    spec_constants: Vec<(u32, u32, u32)>,
+    /// Arrays with a length that's only known once the module is specialized.
+    spec_sized_arrays: Vec<u32>,
+}
+
+/// Reflects the interface of a compiled module.
+pub fn reflect(bytes: &[u8]) -> Result<Module, String> {
+    if bytes.len() & 3 != 0 || bytes.len() < 20 {
+        return Err("not a SPIR-V module".to_owned());
+    }
+    let mut words: Vec<u32> = bytes
+        .chunks(4)
+        .map(|c| u32::from(c[0]) | u32::from(c[1]) << 8 | u32::from(c[2]) << 16 | u32::from(c[3]) << 24)
+        .collect();
+    if words[0] != MAGIC {
+        if words[0].swap_bytes() == MAGIC {
+            words.iter_mut().for_each(|word| *word = word.swap_bytes());
+        } else {
+            return Err("not a SPIR-V module".to_owned());
+        }
+    }
+
+    let mut parser = Parser::default();
+    let mut cursor = 5;
+    while cursor < words.len() {
+        let count = (words[cursor] >> 16) as usize;
+        let opcode = (words[cursor] & 0xffff) as u16;
+        if count == 0 || cursor + count > words.len() {
+            return Err(format!("truncated instruction at word {}", cursor));
+        }
+        let operands = &words[cursor + 1..cursor + count];
+        if operands.len() < min_operands(opcode) {
+            return Err(format!(
+                "opcode {} at word {} needs {} operands, it has {}",
+                opcode,
+                cursor,
+                min_operands(opcode),
+                operands.len()
+            ));
+        }
+        parser.instruction(opcode, operands);
+        cursor += count;
+    }
+    parser.module()
+}
+
+impl Parser {
+    /// `operands` has at least `min_operands(opcode)` words.
+    fn instruction(&mut self, opcode: u16, operands: &[u32]) {
+        let operand = |index: usize| operands[index];
+        match opcode {
+            OP_NAME => {
+                self.names.insert(operand(0), string(&operands[1..]));
+            }
+            OP_MEMBER_NAME => {
+                let name = string(&operands[2..]);
+                self.member_names.insert((operand(0), operand(1)), name);
+            }
+            // Only one entry point per module in our shaders
+            OP_ENTRY_POINT if self.stage.is_none() => {
+                self.stage = match operand(0) {
+                    0 => Some(Stage::Vertex),
+                    1 => Some(Stage::Hull),
+                    2 => Some(Stage::Domain),
+                    3 => Some(Stage::Geometry),
+                    4 => Some(Stage::Fragment),
+                    5 => Some(Stage::Compute),
+                    _ => None,
+                };
+            }
+            OP_TYPE_BOOL => self.add_type(operand(0), RawType::Bool),
+            OP_TYPE_INT => self.add_type(operand(0), RawType::Int(operand(1), operand(2) != 0)),
+            OP_TYPE_FLOAT => self.add_type(operand(0), RawType::Float(operand(1))),
+            OP_TYPE_VECTOR => self.add_type(operand(0), RawType::Vector(operand(1), operand(2))),
+            OP_TYPE_MATRIX => self.add_type(operand(0), RawType::Matrix(operand(1), operand(2))),
+            OP_TYPE_IMAGE => self.add_type(operand(0), RawType::Image(operand(2), operand(6))),
+            OP_TYPE_SAMPLER => self.add_type(operand(0), RawType::Sampler),
+            OP_TYPE_SAMPLED_IMAGE => self.add_type(operand(0), RawType::SampledImage),
+            OP_TYPE_ARRAY => {
+                // Any other constant is a specialization constant or computed from them
+                let length = self.constants.get(&operand(2)).cloned();
+                if length.is_none() {
+                    self.spec_sized_arrays.push(operand(0));
+                }
+                self.add_type(operand(0), RawType::Array(operand(1), length))
+            }
+            OP_TYPE_RUNTIME_ARRAY => self.add_type(operand(0), RawType::Array(operand(1), None)),
+            OP_TYPE_STRUCT => self.add_type(operand(0), RawType::Struct(operands[1..].to_vec())),
+            OP_TYPE_POINTER => self.add_type(operand(0), RawType::Pointer(operand(2))),
+            OP_CONSTANT => {
+                // Only the low word matters, it's used for array lengths
+                self.constants.insert(operand(1), operand(2));
+            }
+            OP_SPEC_CONSTANT_TRUE => self.spec_constants.push((operand(0), operand(1), 1)),
+            OP_SPEC_CONSTANT_FALSE => self.spec_constants.push((operand(0), operand(1), 0)),
+            OP_SPEC_CONSTANT => self.spec_constants.push((operand(0), operand(1), operand(2))),
+            OP_VARIABLE => self.variables.push((operand(0), operand(1), operand(2))),
+            OP_DECORATE => self
+                .decorations
+                .entry(operand(0))
+                .or_default()
+                .push((operand(1), operands[2..].to_vec())),
+            OP_MEMBER_DECORATE => self
+                .member_decorations
+                .entry((operand(0), operand(1)))
+                .or_default()
+                .push((operand(2), operands[3..].to_vec())),
+            _ => {}
+        }
+    }
+
+    fn add_type(&mut self, id: u32, ty: RawType) {
+        self.types.insert(id, ty);
+    }
+
+    fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        find_decoration(self.decorations.get(&id), decoration)
+    }
+
+    fn has_decoration(&self, id: u32, decoration: u32) -> bool {
+        self.decorations
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .any(|(d, _)| *d == decoration)
+    }
+
+    fn member_decoration(&self, id: u32, member: u32, decoration: u32) -> Option<u32> {
+        find_decoration(self.member_decorations.get(&(id, member)), decoration)
+    }
+
+    fn name(&self, id: u32) -> String {
+        self.names.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// `matrix_stride` comes from the member decoration when the type is a struct member.
+    fn resolve(&self, id: u32, matrix_stride: Option<u32>) -> Type {
+        match self.types.get(&id) {
+            Some(RawType::Bool) => Type::Bool,
+            Some(RawType::Int(width, signed)) => Type::Int {
+                width: *width,
+                signed: *signed,
+            },
+            Some(RawType::Float(width)) => Type::Float { width: *width },
+            Some(RawType::Vector(component, count)) => Type::Vector {
+                component: Box::new(self.resolve(*component, None)),
+                count: *count,
+            },
+            Some(RawType::Matrix(column, columns)) => {
+                let column = self.resolve(*column, None);
+                let stride = matrix_stride.unwrap_or_else(|| column.size());
+                Type::Matrix {
+                    column: Box::new(column),
+                    columns: *columns,
+                    stride,
+                }
+            }
+            Some(RawType::Image(dim, sampled)) => Type::Image {
+                dim: *dim,
+                sampled: *sampled,
+            },
+            Some(RawType::Sampler) => Type::Sampler,
+            Some(RawType::SampledImage) => Type::SampledImage,
+            Some(RawType::Array(element, length)) => {
+                let element = self.resolve(*element, matrix_stride);
+                let stride = self
+                    .decoration(id, ARRAY_STRIDE)
+                    .unwrap_or_else(|| element.size());
+                Type::Array {
+                    element: Box::new(element),
+                    length: *length,
+                    stride,
+                }
+            }
+            Some(RawType::Struct(member_types)) => Type::Struct {
+                name: self.name(id),
+                members: member_types
+                    .iter()
+                    .enumerate()
+                    .map(|(index, member_type)| {
+                        let index = index as u32;
+                        let stride = self.member_decoration(id, index, MATRIX_STRIDE);
+                        Member {
+                            name: self
+                                .member_names
+                                .get(&(id, index))
+                                .cloned()
+                                .unwrap_or_else(|| format!("member{}", index)),
+                            offset: self.member_decoration(id, index, OFFSET).unwrap_or(0),
+                            ty: self.resolve(*member_type, stride),
+                        }
+                    })
+                    .collect(),
+            },
+            _ => Type::Unsupported,
+        }
+    }
+
+    /// Is this the `gl_PerVertex` block or a built-in variable?
+    fn is_built_in(&self, variable: u32, pointee: u32) -> bool {
+        if self.has_decoration(variable, BUILT_IN) {
+            return true;
+        }
+        match self.types.get(&pointee) {
+            Some(RawType::Struct(members)) => (0..members.len() as u32)
+                .any(|member| self.member_decoration(pointee, member, BUILT_IN).is_some()),
+            _ => false,
+        }
+    }
+
+    fn module(&self) -> Result<Module, String> {
+        let mut module = Module {
+            stage: self.stage,
+            ..Module::default()
+        };
+
+        for &(pointer, id, storage_class) in &self.variables {
+            let pointee = match self.types.get(&pointer) {
+                Some(RawType::Pointer(pointee)) => *pointee,
+                _ => return Err(format!("variable %{} doesn't have a pointer type", id)),
+            };
+            match storage_class {
+                INPUT | OUTPUT => {
+                    if self.is_built_in(id, pointee) {
+                        continue;
+                    }
+                    let location = self.decoration(id, LOCATION).ok_or_else(|| {
+                        format!("interface variable `{}` has no location", self.name(id))
+                    })?;
+                    let variable = Variable {
+                        name: self.name(id),
+                        location,
+                        ty: self.resolve(pointee, None),
+                    };
+                    if storage_class == INPUT {
+                        module.inputs.push(variable);
+                    } else {
+                        module.outputs.push(variable);
+                    }
+                }
+                PUSH_CONSTANT => module.push_constants = Some(self.resolve(pointee, None)),
+                UNIFORM_CONSTANT | UNIFORM | STORAGE_BUFFER => {
+                    module.descriptors.push(self.descriptor(id, pointee, storage_class)?)
+                }
+                _ => {}
+            }
+        }
+
+        for &(ty, id, default) in &self.spec_constants {
+            // Constants derived from others with OpSpecConstantOp have no id of their own
+            if let Some(spec_id) = self.decoration(id, SPEC_ID) {
+                module.spec_constants.push(SpecConstant {
+                    name: self.name(id),
+                    id: spec_id,
+                    ty: self.resolve(ty, None),
+                    default,
+                });
+            }
+        }
+
+        module.inputs.sort_by_key(|v| v.location);
+        module.outputs.sort_by_key(|v| v.location);
+        module.descriptors.sort_by_key(|d| (d.set, d.binding));
+        module.spec_constants.sort_by_key(|c| c.id);
+        Ok(module)
+    }
+
+    fn descriptor(&self, id: u32, pointee: u32, storage_class: u32) -> Result<Descriptor, String> {
+        // Arrays of descriptors take a single binding
+        let (element, count) = match self.types.get(&pointee) {
+            Some(RawType::Array(_, _)) if self.spec_sized_arrays.contains(&pointee) => {
+                return Err(format!(
+                    "the length of `{}` comes from a specialization constant, the descriptor \
+                     count has to be known at build time",
+                    self.name(id)
+                ));
+            }
+            Some(RawType::Array(element, length)) => (*element, length.unwrap_or(0)),
+            _ => (pointee, 1),
+        };
+        let ty = self.resolve(element, None);
+        let kind = match (&ty, storage_class) {
+            (Type::Struct { .. }, STORAGE_BUFFER) => DescriptorKind::StorageBuffer,
+            (Type::Struct { .. }, _) if self.has_decoration(element, BUFFER_BLOCK) => {
+                DescriptorKind::StorageBuffer
+            }
+            (Type::Struct { .. }, _) if self.has_decoration(element, BLOCK) => {
+                DescriptorKind::UniformBuffer
+            }
+            (Type::SampledImage, _) => DescriptorKind::CombinedImageSampler,
+            (Type::Sampler, _) => DescriptorKind::Sampler,
+            (Type::Image { dim: DIM_SUBPASS_DATA, .. }, _) => DescriptorKind::InputAttachment,
+            (Type::Image { dim: DIM_BUFFER, sampled: 2 }, _) => DescriptorKind::StorageTexelBuffer,
+            (Type::Image { dim: DIM_BUFFER, .. }, _) => DescriptorKind::UniformTexelBuffer,
+            (Type::Image { sampled: 2, .. }, _) => DescriptorKind::StorageImage,
+            (Type::Image { .. }, _) => DescriptorKind::SampledImage,
+            _ => return Err(format!("unsupported resource type for `{}`", self.name(id))),
+        };
+        let name = match self.name(id) {
+            // Blocks without an instance name
+            ref name if name.is_empty() => self.name(element),
+            name => name,
+        };
+        let block = match kind {
+            DescriptorKind::UniformBuffer | DescriptorKind::StorageBuffer => Some(ty),
+            _ => None,
+        };
+        Ok(Descriptor {
+            name,
+            set: self.decoration(id, DESCRIPTOR_SET).unwrap_or(0),
+            binding: self
+                .decoration(id, BINDING)
+                .ok_or_else(|| format!("resource `{}` has no binding", self.name(id)))?,
+            kind,
+            count,
+            block,
+        })
+    }
+}
+
+/// The operands `Parser::instruction` reads of each opcode, strings counting as one word.
+fn min_operands(opcode: u16) -> usize {
+    match opcode {
+        OP_TYPE_BOOL | OP_TYPE_SAMPLER | OP_TYPE_STRUCT => 1,
+        OP_NAME | OP_TYPE_FLOAT | OP_TYPE_SAMPLED_IMAGE | OP_TYPE_RUNTIME_ARRAY => 2,
+        OP_SPEC_CONSTANT_TRUE | OP_SPEC_CONSTANT_FALSE | OP_DECORATE => 2,
+        OP_MEMBER_NAME | OP_ENTRY_POINT | OP_TYPE_INT | OP_TYPE_VECTOR | OP_TYPE_MATRIX => 3,
+        OP_TYPE_ARRAY | OP_TYPE_POINTER | OP_CONSTANT | OP_SPEC_CONSTANT | OP_VARIABLE => 3,
+        OP_MEMBER_DECORATE => 3,
+        OP_TYPE_IMAGE => 8,
+        _ => 0,
+    }
+}
+
+fn find_decoration(list: Option<&Decorations>, decoration: u32) -> Option<u32> {
+    list?
+        .iter()
+        .find(|(d, _)| *d == decoration)
+        .map(|(_, literals)| literals.first().cloned().unwrap_or(0))
+}
+
+/// Decodes a nul terminated literal string.
+fn string(words: &[u32]) -> String {
+    let mut bytes = Vec::with_capacity(words.len() * 4);
+    'words: for word in words {
+        for shift in &[0, 8, 16, 24] {
+            let byte = (word >> shift) as u8;
+            if byte == 0 {
+                break 'words;
+            }
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ids of the types every test module declares
+    const FLOAT: u32 = 1;
+    const VEC2: u32 = 2;
+    const VEC4: u32 = 3;
+    const MAT4: u32 = 4;
+
+    fn op(opcode: u16, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![(operands.len() as u32 + 1) << 16 | u32::from(opcode)];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    /// `ids` followed by `name` as a nul terminated string.
+    fn named(ids: &[u32], name: &str) -> Vec<u32> {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(name.len() / 4 * 4 + 4, 0);
+        let mut operands = ids.to_vec();
+        operands.extend(bytes.chunks(4).map(|c| {
+            u32::from(c[0]) | u32::from(c[1]) << 8 | u32::from(c[2]) << 16 | u32::from(c[3]) << 24
+        }));
+        operands
+    }
+
+    /// A module of the given stage with `instructions` after the scalar and vector types.
+    fn module(stage: u32, instructions: &[Vec<u32>]) -> Vec<u8> {
+        let mut words = vec![MAGIC, 0x0001_0000, 0, 100, 0];
+        words.extend(op(OP_ENTRY_POINT, &named(&[stage, 99], "main")));
+        words.extend(op(OP_TYPE_FLOAT, &[FLOAT, 32]));
+        words.extend(op(OP_TYPE_VECTOR, &[VEC2, FLOAT, 2]));
+        words.extend(op(OP_TYPE_VECTOR, &[VEC4, FLOAT, 4]));
+        words.extend(op(OP_TYPE_MATRIX, &[MAT4, VEC4, 4]));
+        for instruction in instructions {
+            words.extend(instruction);
+        }
+        words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    fn variable(id: u32, pointer: u32, storage_class: u32, pointee: u32, name: &str) -> Vec<u32> {
+        let mut words = op(OP_TYPE_POINTER, &[pointer, storage_class, pointee]);
+        words.extend(op(OP_VARIABLE, &[pointer, id, storage_class]));
+        words.extend(op(OP_NAME, &named(&[id], name)));
+        words
+    }
+
+    #[test]
+    fn inputs_and_outputs_by_location() {
+        let spirv = module(
+            0,
+            &[
+                variable(10, 20, INPUT, VEC4, "color"),
+                op(OP_DECORATE, &[10, LOCATION, 2]),
+                variable(11, 21, INPUT, VEC2, "position"),
+                op(OP_DECORATE, &[11, LOCATION, 0]),
+                variable(12, 22, OUTPUT, VEC4, "fragColor"),
+                op(OP_DECORATE, &[12, LOCATION, 0]),
+                // Built-ins aren't part of the interface
+                variable(13, 23, OUTPUT, VEC4, "gl_Position"),
+                op(OP_DECORATE, &[13, BUILT_IN, 0]),
+            ],
+        );
+        let module = reflect(&spirv).unwrap();
+        assert_eq!(module.stage, Some(Stage::Vertex));
+        let vec = |count| Type::Vector {
+            component: Box::new(Type::Float { width: 32 }),
+            count,
+        };
+        let variable = |name: &str, location, ty| Variable {
+            name: name.to_owned(),
+            location,
+            ty,
+        };
+        assert_eq!(
+            module.inputs,
+            [
+                variable("position", 0, vec(2)),
+                variable("color", 2, vec(4)),
+            ]
+        );
+        assert_eq!(module.outputs, [variable("fragColor", 0, vec(4))]);
+    }
+
+    #[test]
+    fn interface_variables_need_a_location() {
+        let spirv = module(4, &[variable(10, 20, INPUT, VEC4, "color")]);
+        let e = reflect(&spirv).unwrap_err();
+        assert!(e.contains("color"), "{}", e);
+    }
+
+    #[test]
+    fn descriptor_sets_and_bindings() {
+        let spirv = module(
+            4,
+            &[
+                op(OP_TYPE_STRUCT, &[30, MAT4]),
+                op(OP_NAME, &named(&[30], "Camera")),
+                op(OP_MEMBER_NAME, &named(&[30, 0], "view")),
+                op(OP_MEMBER_DECORATE, &[30, 0, OFFSET, 0]),
+                op(OP_MEMBER_DECORATE, &[30, 0, MATRIX_STRIDE, 16]),
+                op(OP_DECORATE, &[30, BLOCK]),
+                variable(31, 32, UNIFORM, 30, "camera"),
+                op(OP_DECORATE, &[31, DESCRIPTOR_SET, 1]),
+                op(OP_DECORATE, &[31, BINDING, 2]),
+                // A 2D sampled image, combined with a sampler
+                op(OP_TYPE_IMAGE, &[40, FLOAT, 1, 0, 0, 0, 1, 0]),
+                op(OP_TYPE_SAMPLED_IMAGE, &[41, 40]),
+                variable(42, 43, UNIFORM_CONSTANT, 41, "albedo"),
+                op(OP_DECORATE, &[42, DESCRIPTOR_SET, 0]),
+                op(OP_DECORATE, &[42, BINDING, 3]),
+            ],
+        );
+        let module = reflect(&spirv).unwrap();
+        let found: Vec<_> = module
+            .descriptors
+            .iter()
+            .map(|d| (d.name.as_str(), d.set, d.binding, d.kind, d.count))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("albedo", 0, 3, DescriptorKind::CombinedImageSampler, 1),
+                ("camera", 1, 2, DescriptorKind::UniformBuffer, 1),
+            ]
+        );
+        assert_eq!(module.descriptors[0].block, None);
+        match &module.descriptors[1].block {
+            Some(Type::Struct { name, members }) => {
+                assert_eq!(name, "Camera");
+                assert_eq!(members[0].name, "view");
+            }
+            block => panic!("expected the Camera block, got {:?}", block),
+        }
+    }
+
+    #[test]
+    fn spec_sized_descriptor_arrays_are_an_error() {
+        let spirv = module(
+            4,
+            &[
+                op(OP_TYPE_IMAGE, &[40, FLOAT, 1, 0, 0, 0, 1, 0]),
+                op(OP_TYPE_SAMPLED_IMAGE, &[41, 40]),
+                op(OP_TYPE_INT, &[42, 32, 0]),
+                op(OP_SPEC_CONSTANT, &[42, 43, 4]),
+                op(OP_DECORATE, &[43, SPEC_ID, 0]),
+                op(OP_TYPE_ARRAY, &[44, 41, 43]),
+                variable(45, 46, UNIFORM_CONSTANT, 44, "layers"),
+                op(OP_DECORATE, &[45, DESCRIPTOR_SET, 0]),
+                op(OP_DECORATE, &[45, BINDING, 0]),
+            ],
+        );
+        let e = reflect(&spirv).unwrap_err();
+        assert!(e.contains("`layers` comes from a specialization constant"), "{}", e);
+
+        // Plain constants give the count
+        let spirv = module(
+            4,
+            &[
+                op(OP_TYPE_IMAGE, &[40, FLOAT, 1, 0, 0, 0, 1, 0]),
+                op(OP_TYPE_SAMPLED_IMAGE, &[41, 40]),
+                op(OP_TYPE_INT, &[42, 32, 0]),
+                op(OP_CONSTANT, &[42, 43, 4]),
+                op(OP_TYPE_ARRAY, &[44, 41, 43]),
+                variable(45, 46, UNIFORM_CONSTANT, 44, "layers"),
+                op(OP_DECORATE, &[45, DESCRIPTOR_SET, 0]),
+                op(OP_DECORATE, &[45, BINDING, 0]),
+            ],
+        );
+        assert_eq!(reflect(&spirv).unwrap().descriptors[0].count, 4);
+    }
+
+    #[test]
+    fn push_constant_block_offsets_and_size() {
+        let spirv = module(
+            4,
+            &[
+                op(OP_TYPE_STRUCT, &[50, VEC4, MAT4, FLOAT]),
+                op(OP_NAME, &named(&[50], "Inputs")),
+                op(OP_MEMBER_NAME, &named(&[50, 0], "color")),
+                op(OP_MEMBER_NAME, &named(&[50, 1], "transform")),
+                op(OP_MEMBER_NAME, &named(&[50, 2], "time")),
+                op(OP_MEMBER_DECORATE, &[50, 0, OFFSET, 0]),
+                op(OP_MEMBER_DECORATE, &[50, 1, OFFSET, 16]),
+                op(OP_MEMBER_DECORATE, &[50, 1, MATRIX_STRIDE, 16]),
+                op(OP_MEMBER_DECORATE, &[50, 2, OFFSET, 80]),
+                op(OP_DECORATE, &[50, BLOCK]),
+                variable(51, 52, PUSH_CONSTANT, 50, "inputs"),
+            ],
+        );
+        let module = reflect(&spirv).unwrap();
+        let block = module.push_constants.unwrap();
+        assert_eq!(block.size(), 84);
+        let members = match &block {
+            Type::Struct { members, .. } => members,
+            _ => panic!("expected a struct, got {:?}", block),
+        };
+        let layout: Vec<_> = members
+            .iter()
+            .map(|m| (m.name.as_str(), m.offset, m.ty.to_string()))
+            .collect();
+        assert_eq!(
+            layout,
+            [
+                ("color", 0, "vec4".to_owned()),
+                ("transform", 16, "mat4".to_owned()),
+                ("time", 80, "float".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn short_instructions_are_an_error() {
+        // OpName without its string
+        let spirv = module(4, &[op(OP_NAME, &[10])]);
+        let e = reflect(&spirv).unwrap_err();
+        assert!(e.contains("needs 2 operands, it has 1"), "{}", e);
+
+        let spirv = module(4, &[op(OP_MEMBER_DECORATE, &[10, 0])]);
+        assert!(reflect(&spirv).is_err());
+        let spirv = module(4, &[op(OP_TYPE_IMAGE, &[40, FLOAT, 1])]);
+        assert!(reflect(&spirv).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! Checks that the outputs of a vertex shader line up with the inputs of the fragment shader it
+//! gets paired with, so a mismatch fails the build instead of the pipeline creation.
+//!
+//! Shaders are paired by stem, `simple.vert` with `simple.frag`. A permutation like
+//! `simple.high.frag` pairs with `simple.high.vert` when there is one and with `simple.vert`
+//! otherwise. Other pairings go in the `shader_pairs.txt` manifest of the shader root, one
+//! `<vertex> <fragment>` pair per line.
+
+use crate::diagnostics::Diagnostic;
+use crate::reflect::{Module, Stage};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST: &str = "shader_pairs.txt";
+
+/// A compiled shader variant along with the file it was compiled from.
+pub struct Shader<'a> {
+    pub name: &'a str,
+    pub path: &'a Path,
+    pub module: &'a Module,
+}
+
+/// The `(vertex, fragment)` pairs to check.
+pub fn pairs(
+    shaders: &[Shader],
+    manifest: Option<(&Path, &str)>,
+) -> Result<Vec<(String, String)>, Box<Diagnostic>> {
+    let stem = |name: &str| name[..name.rfind('.').unwrap_or(name.len())].to_owned();
+    let base_stem = |name: &str| name[..name.find('.').unwrap_or(name.len())].to_owned();
+    let by_stem = |stage: Stage| -> HashMap<String, &str> {
+        shaders
+            .iter()
+            .filter(|shader| shader.module.stage == Some(stage))
+            .map(|shader| (stem(shader.name), shader.name))
+            .collect()
+    };
+    let vertex_shaders = by_stem(Stage::Vertex);
+    let fragment_shaders = by_stem(Stage::Fragment);
+
+    let mut pairs = vec![];
+    let mut push = |vertex: &str, fragment: &str| {
+        let pair = (vertex.to_owned(), fragment.to_owned());
+        if !pairs.contains(&pair) {
+            pairs.push(pair);
+        }
+    };
+    for (fragment_stem, fragment) in &fragment_shaders {
+        let vertex = vertex_shaders
+            .get(fragment_stem)
+            .or_else(|| vertex_shaders.get(&base_stem(fragment_stem)));
+        if let Some(vertex) = vertex {
+            push(vertex, fragment);
+        }
+    }
+    for (vertex_stem, vertex) in &vertex_shaders {
+        if !fragment_shaders.contains_key(vertex_stem) {
+            if let Some(fragment) = fragment_shaders.get(&base_stem(vertex_stem)) {
+                push(vertex, fragment);
+            }
+        }
+    }
+
+    if let Some((path, manifest)) = manifest {
+        for (index, line) in manifest.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| {
+                Box::new(Diagnostic {
+                    path: Some(path.to_owned()),
+                    line: index + 1,
+                    source_line: Some(line.to_owned()),
+                    ..Diagnostic::error(message)
+                })
+            };
+            let names: Vec<&str> = line.split_whitespace().collect();
+            if names.len() != 2 {
+                return Err(error("expected `<vertex shader> <fragment shader>`".to_owned()));
+            }
+            for (name, stage) in names.iter().zip(&[Stage::Vertex, Stage::Fragment]) {
+                match shaders.iter().find(|shader| shader.name == *name) {
+                    Some(shader) if shader.module.stage == Some(*stage) => {}
+                    Some(_) => {
+                        let stage = format!("{:?}", stage).to_lowercase();
+                        return Err(error(format!("`{}` isn't a {} shader", name, stage)));
+                    }
+                    None => return Err(error(format!("unknown shader `{}`", name))),
+                }
+            }
+            push(names[0], names[1]);
+        }
+    }
+
+    pairs.sort();
+    Ok(pairs)
+}
+
+/// Every fragment input needs a vertex output of the same type at the same location.
+pub fn check(vertex: &Shader, fragment: &Shader) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for input in &fragment.module.inputs {
+        let message = match vertex.module.outputs.iter().find(|o| o.location == input.location) {
+            None => format!(
+                "`{}` reads `{} {}` at location {}, but `{}` doesn't write that location",
+                fragment.name, input.ty, input.name, input.location, vertex.name
+            ),
+            Some(output) if output.ty != input.ty => format!(
+                "`{}` reads `{} {}` at location {}, but `{}` writes `{} {}` there",
+                fragment.name,
+                input.ty,
+                input.name,
+                input.location,
+                vertex.name,
+                output.ty,
+                output.name
+            ),
+            Some(_) => continue,
+        };
+        let (line, source_line) = declaration(fragment.path, &input.name).unwrap_or((0, None));
+        diagnostics.push(Diagnostic {
+            path: Some(fragment.path.to_owned()),
+            line,
+            source_line,
+            ..Diagnostic::error(message)
+        });
+    }
+    diagnostics
+}
+
+/// Finds the `in` declaration of a variable to point the message at.
+fn declaration(path: &Path, name: &str) -> Option<(usize, Option<String>)> {
+    let source = std::fs::read_to_string(path).ok()?;
+    source.lines().enumerate().find_map(|(index, line)| {
+        let words: Vec<&str> = line
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .collect();
+        if words.contains(&"in") && words.contains(&name) {
+            Some((index + 1, Some(line.trim_end().to_owned())))
+        } else {
+            None
+        }
+    })
+}
+
+/// The manifest path and contents, if there is one.
+pub fn read_manifest(root: &Path) -> std::io::Result<Option<(PathBuf, String)>> {
+    let path = root.join(MANIFEST);
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => Ok(Some((path, contents))),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::{Type, Variable};
+
+    fn module(stage: Stage, inputs: Vec<Variable>, outputs: Vec<Variable>) -> Module {
+        Module {
+            stage: Some(stage),
+            inputs,
+            outputs,
+            ..Module::default()
+        }
+    }
+
+    fn vec4(name: &str, location: u32) -> Variable {
+        let component = Box::new(Type::Float { width: 32 });
+        Variable {
+            name: name.to_owned(),
+            location,
+            ty: Type::Vector { component, count: 4 },
+        }
+    }
+
+    fn named<'a>(modules: &'a [(&'a str, Module)]) -> Vec<Shader<'a>> {
+        modules
+            .iter()
+            .map(|(name, module)| Shader {
+                name,
+                path: Path::new(name),
+                module,
+            })
+            .collect()
+    }
+
+    fn pair(vertex: &str, fragment: &str) -> (String, String) {
+        (vertex.to_owned(), fragment.to_owned())
+    }
+
+    #[test]
+    fn shaders_pair_by_stem() {
+        let modules = [
+            ("simple.vert", module(Stage::Vertex, vec![], vec![])),
+            ("simple.frag", module(Stage::Fragment, vec![], vec![])),
+            // Falls back to `simple.vert`
+            ("simple.low.frag", module(Stage::Fragment, vec![], vec![])),
+            // Only pairs with its own vertex shader, not with `simple.vert` as well
+            ("simple.high.vert", module(Stage::Vertex, vec![], vec![])),
+            ("simple.high.frag", module(Stage::Fragment, vec![], vec![])),
+            // Unpaired on either side
+            ("lonely.vert", module(Stage::Vertex, vec![], vec![])),
+            ("alone.frag", module(Stage::Fragment, vec![], vec![])),
+        ];
+        assert_eq!(
+            pairs(&named(&modules), None).unwrap(),
+            [
+                pair("simple.high.vert", "simple.high.frag"),
+                pair("simple.vert", "simple.frag"),
+                pair("simple.vert", "simple.low.frag"),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_manifest_adds_pairs() {
+        let modules = [
+            ("simple.vert", module(Stage::Vertex, vec![], vec![])),
+            ("simple.frag", module(Stage::Fragment, vec![], vec![])),
+            ("fullscreen.vert", module(Stage::Vertex, vec![], vec![])),
+            ("blur.frag", module(Stage::Fragment, vec![], vec![])),
+        ];
+        let shaders = named(&modules);
+        let path = Path::new(MANIFEST);
+        // Repeating a pair found by stem doesn't check it twice
+        let manifest = "# Post processing\nfullscreen.vert blur.frag\n\nsimple.vert simple.frag\n";
+        assert_eq!(
+            pairs(&shaders, Some((path, manifest))).unwrap(),
+            [
+                pair("fullscreen.vert", "blur.frag"),
+                pair("simple.vert", "simple.frag"),
+            ]
+        );
+
+        let error = |manifest| *pairs(&shaders, Some((path, manifest))).unwrap_err();
+        let swapped = error("simple.vert simple.frag\nblur.frag fullscreen.vert\n");
+        assert_eq!(swapped.message, "`blur.frag` isn't a vertex shader");
+        assert_eq!((swapped.path.as_deref(), swapped.line), (Some(path), 2));
+        let unknown = error("fullscreen.vert sharpen.frag");
+        assert_eq!(unknown.message, "unknown shader `sharpen.frag`");
+        let malformed = error("fullscreen.vert");
+        assert_eq!(malformed.message, "expected `<vertex shader> <fragment shader>`");
+    }
+
+    #[test]
+    fn fragment_inputs_need_matching_vertex_outputs() {
+        let mut matching = vec![vec4("color", 0)];
+        let modules = [
+            ("simple.vert", module(Stage::Vertex, vec![], matching.clone())),
+            ("simple.frag", module(Stage::Fragment, matching.clone(), vec![])),
+        ];
+        let shaders = named(&modules);
+        assert!(check(&shaders[0], &shaders[1]).is_empty());
+
+        matching[0].ty = Type::Float { width: 32 };
+        let modules = [
+            ("simple.vert", module(Stage::Vertex, vec![], matching)),
+            ("simple.frag", module(Stage::Fragment, vec![vec4("color", 0), vec4("uv", 1)], vec![])),
+        ];
+        let shaders = named(&modules);
+        let messages: Vec<String> = check(&shaders[0], &shaders[1])
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("but `simple.vert` writes `float color` there"));
+        assert!(messages[1].contains("at location 1, but `simple.vert` doesn't write that"));
+    }
+}
@@ -0,0 +1,66 @@
+//! The smallest app: clears the window to a color going around the hue circle, a turn every
+//! six seconds. Space pauses it, and while it's paused the loop sleeps instead of drawing the
+//! same frame over.
+
+use nicegfx::{shaders, timestep};
+use nicegfx::{App, Frame, InputMap, RedrawMode, Runner, ShaderPair, UpdateTime, UserInput};
+
+use winit::dpi::LogicalSize;
+use winit::VirtualKeyCode;
+
+use log::Level;
+
+use std::error::Error;
+use std::time::Duration;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    simple_logger::init_with_level(Level::Warn).unwrap();
+    let runner = Runner::new("NiceGFX clear color", LogicalSize::new(800.0, 600.0))?
+        .with_redraw_mode(RedrawMode::OnDemand);
+    runner.run(&mut ClearColor::default())
+}
+
+#[derive(Default)]
+struct ClearColor {
+    paused: bool,
+    hue_time: Duration,
+    drawn_hue_time: Option<Duration>,
+}
+
+impl App for ClearColor {
+    fn shaders(&self) -> ShaderPair {
+        // Nothing gets drawn with them
+        shaders::TRIANGLE
+    }
+
+    fn update(&mut self, input: &UserInput, _input_map: &InputMap) {
+        if input.just_pressed(VirtualKeyCode::Space) {
+            self.paused = !self.paused;
+        }
+    }
+
+    fn fixed_update(&mut self, time: &UpdateTime) {
+        if !self.paused {
+            self.hue_time += time.step;
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) -> Result<(), &'static str> {
+        self.drawn_hue_time = Some(self.hue_time);
+        let turns = timestep::seconds(self.hue_time) / 6.0;
+        frame.hal_state.draw_clear_frame(hue(turns.fract()))
+    }
+
+    fn needs_redraw(&self) -> bool {
+        !self.paused || self.drawn_hue_time != Some(self.hue_time)
+    }
+}
+
+/// The fully saturated color `turns` around the hue circle, starting at red.
+fn hue(turns: f32) -> [f32; 4] {
+    let channel = |offset: f32| {
+        let distance = ((turns + offset).fract() * 6.0 - 3.0).abs();
+        (distance - 1.0).max(0.0).min(1.0)
+    };
+    [channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0), 1.0]
+}
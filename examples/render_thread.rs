@@ -0,0 +1,93 @@
+//! The triangle example with the rendering on a thread of its own. `--frame-delay <ms>` makes
+//! every frame that much slower, the mouse still gets tracked at full speed and the window
+//! still moves smoothly.
+//!
+//! `cargo run --example render_thread --features vulkan -- [--frame-delay <ms>]`
+
+use nicegfx::{shaders, timestep};
+use nicegfx::{Frame, InputMap, Renderer, Runner, ShaderPair, Simulation, UserInput};
+
+use winit::dpi::LogicalSize;
+
+use log::Level;
+
+use std::error::Error;
+use std::time::Duration;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    simple_logger::init_with_level(Level::Warn).unwrap();
+    let runner = Runner::new("NiceGFX render thread", LogicalSize::new(800.0, 600.0))?;
+    let (runner, rest) = runner.with_args(std::env::args().skip(1))?;
+
+    let mut frame_delay = Duration::from_secs(0);
+    let mut args = rest.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frame-delay" => {
+                let ms = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or("--frame-delay needs a number of milliseconds")?;
+                frame_delay = Duration::from_millis(ms);
+            }
+            _ => return Err(format!("Unknown option {}", arg).into()),
+        }
+    }
+
+    runner.run_threaded(&mut Tracker::default(), TriangleRenderer { frame_delay })
+}
+
+/// Follows the mouse, sizes and positions are logical.
+#[derive(Debug, Clone, Copy, Default)]
+struct Tracker {
+    frame_width: f64,
+    frame_height: f64,
+    mouse_x: f64,
+    mouse_y: f64,
+}
+
+impl Simulation for Tracker {
+    type Snapshot = Tracker;
+
+    fn update(&mut self, input: &UserInput, _input_map: &InputMap) {
+        if let Some((x, y)) = input.new_mouse_position {
+            self.mouse_x = x;
+            self.mouse_y = y;
+        }
+    }
+
+    fn on_resize(&mut self, width: f64, height: f64) {
+        self.frame_width = width;
+        self.frame_height = height;
+    }
+
+    fn snapshot(&mut self) -> Tracker {
+        *self
+    }
+}
+
+struct TriangleRenderer {
+    frame_delay: Duration,
+}
+
+impl Renderer for TriangleRenderer {
+    type Snapshot = Tracker;
+
+    fn shaders(&self) -> ShaderPair {
+        shaders::TRIANGLE
+    }
+
+    fn render(&mut self, tracker: &Tracker, frame: &mut Frame) -> Result<(), &'static str> {
+        std::thread::sleep(self.frame_delay);
+        let r = (tracker.mouse_x / tracker.frame_width) as f32;
+        let g = (tracker.mouse_y / tracker.frame_height) as f32;
+        let b = (r + g) * 0.3;
+        let pulse = (timestep::seconds(frame.time.total) * 3.0).sin() * 0.25 + 0.75;
+        let triangle = shaders::simple_frag::PushConstants {
+            color: [0.5 * pulse, 0.5 * pulse, pulse, 1.0],
+        };
+        frame
+            .hal_state
+            .draw_vertices_frame([r, g, b, 1.0], 3, shaders::as_words(&triangle))
+    }
+}
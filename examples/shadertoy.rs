@@ -0,0 +1,43 @@
+//! Runs a Shadertoy-style effect over the whole window, the built-in one without a file.
+//!
+//! `cargo run --example shadertoy --features vulkan,hot-reload -- [effect.frag]
+//! [--channel<N> <image>]...` along with the options of the `triangle` example.
+
+use nicegfx::{Runner, Shadertoy};
+
+use winit::dpi::LogicalSize;
+
+use log::Level;
+
+use std::error::Error;
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    simple_logger::init_with_level(Level::Warn).unwrap();
+    let runner = Runner::new("NiceGFX shadertoy", LogicalSize::new(800.0, 600.0))?;
+    let (runner, rest) = runner.with_args(std::env::args().skip(1))?;
+
+    let mut effect = None;
+    let mut channels: [Option<PathBuf>; 4] = Default::default();
+    let mut args = rest.into_iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--channel") {
+            let index = arg["--channel".len()..]
+                .parse::<usize>()
+                .ok()
+                .filter(|&index| index < channels.len())
+                .ok_or_else(|| format!("Unknown option {}", arg))?;
+            let image = args
+                .next()
+                .ok_or_else(|| format!("{} needs an image", arg))?;
+            channels[index] = Some(PathBuf::from(image));
+        } else if arg.starts_with("--") || effect.is_some() {
+            return Err(format!("Unknown option {}", arg).into());
+        } else {
+            effect = Some(PathBuf::from(arg));
+        }
+    }
+
+    let shader_root = concat!(env!("CARGO_MANIFEST_DIR"), "/pre_assets/shaders");
+    runner.run(&mut Shadertoy::new(shader_root, effect, &channels)?)
+}
@@ -0,0 +1,83 @@
+//! Draws an image on a quad in the middle of the window, keeping its aspect ratio. Nothing
+//! moves, so it only draws when the window changes.
+//!
+//! `cargo run --example textured_quad --features vulkan -- [image]`
+
+use nicegfx::shaders::{self, quad_frag, quad_vert};
+use nicegfx::{App, Frame, HalState, InputMap, RedrawMode, Runner, ShaderPair, UserInput};
+
+use image::RgbaImage;
+
+use winit::dpi::LogicalSize;
+
+use log::Level;
+
+use std::error::Error;
+
+const DEFAULT_IMAGE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/textures/ebin.jpg");
+
+/// How much of the window the image may cover.
+const MARGIN_SCALE: f32 = 0.9;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    simple_logger::init_with_level(Level::Warn).unwrap();
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_IMAGE.to_owned());
+    let image = image::open(&path)
+        .map_err(|e| format!("Couldn't load {}: {}", path, e))?
+        .to_rgba();
+    let runner = Runner::new("NiceGFX textured quad", LogicalSize::new(800.0, 600.0))?
+        .with_redraw_mode(RedrawMode::OnDemand);
+    runner.run(&mut TexturedQuad {
+        image,
+        window_size: (0.0, 0.0),
+        drawn_size: None,
+    })
+}
+
+struct TexturedQuad {
+    image: RgbaImage,
+    window_size: (f64, f64),
+    drawn_size: Option<(f64, f64)>,
+}
+
+impl App for TexturedQuad {
+    fn shaders(&self) -> ShaderPair {
+        shaders::QUAD
+    }
+
+    fn init(&mut self, hal_state: &mut HalState) -> Result<(), &'static str> {
+        hal_state.set_texture(quad_frag::bindings::PICTURE, &self.image)
+    }
+
+    fn update(&mut self, _input: &UserInput, _input_map: &InputMap) {}
+
+    fn render(&mut self, frame: &mut Frame) -> Result<(), &'static str> {
+        self.drawn_size = Some(self.window_size);
+        let (window_width, window_height) = self.window_size;
+        let window_aspect = (window_width / window_height.max(1.0)) as f32;
+        let image_aspect = self.image.width() as f32 / self.image.height().max(1) as f32;
+        let scale = if image_aspect > window_aspect {
+            [1.0, window_aspect / image_aspect]
+        } else {
+            [image_aspect / window_aspect, 1.0]
+        };
+        let push_constants = quad_vert::PushConstants {
+            scale: [scale[0] * MARGIN_SCALE, scale[1] * MARGIN_SCALE],
+        };
+        frame.hal_state.draw_vertices_frame(
+            [0.1, 0.1, 0.1, 1.0],
+            6,
+            shaders::as_words(&push_constants),
+        )
+    }
+
+    fn on_resize(&mut self, width: f64, height: f64) {
+        self.window_size = (width, height);
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.drawn_size != Some(self.window_size)
+    }
+}
@@ -0,0 +1,133 @@
+//! The `simple` triangle over a background following the mouse. `invert` switches to a pipeline
+//! specialized to invert its color. With the `hot-reload` feature, edits to its shaders in
+//! `pre_assets/shaders` show up while it runs.
+//!
+//! `cargo run --example triangle --features vulkan[,hot-reload] -- [--bindings <file.toml>]
+//! [--record <file> | --replay <file>]`
+
+#[cfg(feature = "hot-reload")]
+use nicegfx::shader_reload::ShaderReloader;
+use nicegfx::shaders::{self, simple_frag, SpecConstants, SpecializationKey};
+use nicegfx::timestep;
+use nicegfx::{App, Frame, HalState, InputMap, Runner, ShaderPair, UserInput, WindowConfig};
+
+use log::Level;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use std::error::Error;
+
+/// Where the sources of the shaders are, to reload them from.
+#[cfg(feature = "hot-reload")]
+const SHADER_ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/pre_assets/shaders");
+
+fn main() -> Result<(), Box<dyn Error>> {
+    simple_logger::init_with_level(Level::Warn).unwrap();
+    // Opens where it was closed last time
+    let runner = Runner::from_config(WindowConfig {
+        title: "NiceGFX triangle".to_owned(),
+        remember_geometry: true,
+        ..WindowConfig::default()
+    })?;
+    let (runner, rest) = runner.with_args(std::env::args().skip(1))?;
+    if let Some(arg) = rest.first() {
+        return Err(format!("Unknown option {}", arg).into());
+    }
+    runner.run(&mut Triangle::new())
+}
+
+/// Sizes and positions are logical.
+struct Triangle {
+    frame_width: f64,
+    frame_height: f64,
+    mouse_x: f64,
+    mouse_y: f64,
+    inverted: bool,
+    #[cfg(feature = "hot-reload")]
+    shader_reloader: ShaderReloader,
+}
+
+impl Triangle {
+    fn new() -> Self {
+        Self {
+            frame_width: 0.0,
+            frame_height: 0.0,
+            mouse_x: 0.0,
+            mouse_y: 0.0,
+            inverted: false,
+            #[cfg(feature = "hot-reload")]
+            shader_reloader: ShaderReloader::new(SHADER_ROOT),
+        }
+    }
+
+    /// Swaps in the current shader sources, keeping the old pipeline if any of them is broken.
+    #[cfg(feature = "hot-reload")]
+    fn reload_shaders(&self, hal_state: &mut HalState) {
+        let vertex = self.shader_reloader.compile(&shaders::simple_vert::INTERFACE);
+        let fragment = self.shader_reloader.compile(&simple_frag::INTERFACE);
+        if let (Some(vertex), Some(fragment)) = (vertex, fragment) {
+            match hal_state.replace_shaders(Some(vertex), Some(fragment)) {
+                Ok(()) => info!("Reloaded the shaders"),
+                Err(e) => error!("Couldn't reload the shaders: {}", e),
+            }
+        }
+    }
+}
+
+impl App for Triangle {
+    fn shaders(&self) -> ShaderPair {
+        shaders::TRIANGLE
+    }
+
+    fn init(&mut self, _hal_state: &mut HalState) -> Result<(), &'static str> {
+        // The new state starts out with the shaders embedded at build time
+        #[cfg(feature = "hot-reload")]
+        self.shader_reloader.force();
+        Ok(())
+    }
+
+    fn update(&mut self, input: &UserInput, input_map: &InputMap) {
+        if let Some((x, y)) = input.new_mouse_position {
+            self.mouse_x = x;
+            self.mouse_y = y;
+        }
+        if input_map.just_activated(input, "invert") {
+            self.inverted = !self.inverted;
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) -> Result<(), &'static str> {
+        #[cfg(feature = "hot-reload")]
+        {
+            if self.shader_reloader.changed() {
+                self.reload_shaders(frame.hal_state);
+            }
+        }
+        let r = (self.mouse_x / self.frame_width) as f32;
+        let g = (self.mouse_y / self.frame_height) as f32;
+        let b = (r + g) * 0.3;
+        let a = 1.0;
+        // Pulses once every couple of seconds
+        let pulse = (timestep::seconds(frame.time.total) * 3.0).sin() * 0.25 + 0.75;
+        let triangle = simple_frag::PushConstants {
+            color: [0.5 * pulse, 0.5 * pulse, pulse, 1.0],
+        };
+        // The inverted pipeline gets built the first time it's asked for, both stay cached
+        let mut fragment = SpecConstants::default();
+        if self.inverted {
+            fragment.set(simple_frag::spec_constants::INVERT, true);
+        }
+        frame.hal_state.specialize(SpecializationKey {
+            fragment,
+            ..SpecializationKey::default()
+        })?;
+        frame
+            .hal_state
+            .draw_vertices_frame([r, g, b, a], 3, shaders::as_words(&triangle))
+    }
+
+    fn on_resize(&mut self, width: f64, height: f64) {
+        self.frame_width = width;
+        self.frame_height = height;
+    }
+}
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D picture;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 target;
+
+void main() {
+    target = texture(picture, uv);
+}
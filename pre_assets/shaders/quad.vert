@@ -0,0 +1,24 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+    vec2 scale;
+} push;
+
+layout(location = 0) out vec2 uv;
+
+// Two triangles making a quad, no vertex buffer needed
+vec2 corners[6] = vec2[](
+    vec2(0.0, 0.0),
+    vec2(1.0, 0.0),
+    vec2(1.0, 1.0),
+    vec2(0.0, 0.0),
+    vec2(1.0, 1.0),
+    vec2(0.0, 1.0)
+);
+
+void main() {
+    vec2 corner = corners[gl_VertexIndex];
+    uv = corner;
+    gl_Position = vec4((corner * 2.0 - 1.0) * push.scale, 0.0, 1.0);
+}
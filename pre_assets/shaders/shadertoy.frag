@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#include "shadertoy.glsl"
+
+// What the `shadertoy` example shows when it isn't given an effect file
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = fragCoord / iResolution.xy;
+    vec3 color = 0.5 + 0.5 * cos(iTime + uv.xyx + vec3(0.0, 2.0, 4.0));
+    if (iMouse.z > 0.0 && distance(fragCoord, iMouse.xy) < 10.0) {
+        color = vec3(1.0);
+    }
+    fragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,28 @@
+// The inputs of a Shadertoy-style effect. `shadertoy.frag` includes this, and the effect file
+// passed to the `shadertoy` example gets it pasted in front. An effect only defines `mainImage`.
+
+layout(push_constant) uniform ShadertoyInputs {
+    // xy: the cursor while a button is held, z: where it was pressed, negative once released,
+    // w: same, but only positive on the frame the button went down
+    vec4 iMouse;
+    // Viewport size in pixels, z is the pixel aspect ratio
+    vec3 iResolution;
+    // Seconds since the effect started
+    float iTime;
+    float iTimeDelta;
+    int iFrame;
+};
+
+layout(set = 0, binding = 0) uniform sampler2D iChannel0;
+layout(set = 0, binding = 1) uniform sampler2D iChannel1;
+layout(set = 0, binding = 2) uniform sampler2D iChannel2;
+layout(set = 0, binding = 3) uniform sampler2D iChannel3;
+
+layout(location = 0) out vec4 shadertoyColor;
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord);
+
+void main() {
+    // Shadertoy puts the origin at the bottom left
+    mainImage(shadertoyColor, vec2(gl_FragCoord.x, iResolution.y - gl_FragCoord.y));
+}
@@ -1,8 +1,15 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
 
+layout(push_constant) uniform PushConstants {
+    vec4 color;
+} push;
+
+// Picked per pipeline, see `HalState::specialize`
+layout(constant_id = 0) const bool INVERT = false;
+
 layout(location = 0) out vec4 target;
 
 void main() {
-    target = vec4(0.5, 0.5, 1.0, 1.0);
+    target = INVERT ? vec4(1.0 - push.color.rgb, push.color.a) : push.color;
 }
\ No newline at end of file
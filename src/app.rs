@@ -0,0 +1,436 @@
+//! The main loop, written once. An `App` says what to draw and how to react to input, the
+//! `Runner` owns the window and the `HalState` and calls into it every frame.
+
+use winit::dpi::LogicalSize;
+use winit::{EventsLoop, EventsLoopProxy};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::frame_limiter::{FrameLimiter, FrameStats, STATS_INTERVAL};
+use crate::hal_state::HalState;
+use crate::input_map::{self, InputMap};
+use crate::input_record::{Recorder, Replay};
+use crate::render_thread::{RenderThread, Renderer, Simulation};
+use crate::shaders::ShaderPair;
+use crate::timestep::{FixedTimestep, RenderTime, UpdateTime};
+use crate::user_input::UserInput;
+use crate::winit_state::{self, WindowConfig, WindowMode, WinitState};
+
+use std::error::Error;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The simulation runs at 60 steps a second whatever the frame rate.
+const UPDATE_RATE: u32 = 60;
+/// Updates a single frame can run before the simulation falls behind.
+const MAX_UPDATES_PER_FRAME: u32 = 8;
+
+pub trait App {
+    /// The shaders the `HalState` of the app is created with.
+    fn shaders(&self) -> ShaderPair;
+
+    /// Called with the `HalState` once it's created, before the first frame. It starts out
+    /// with placeholder textures and the shaders embedded at build time, which resizes keep.
+    fn init(&mut self, _hal_state: &mut HalState) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    /// Called once a frame with everything that happened since the last one.
+    fn update(&mut self, input: &UserInput, input_map: &InputMap);
+
+    /// Called zero or more times a frame, once per fixed step of the simulation.
+    fn fixed_update(&mut self, _time: &UpdateTime) {}
+
+    fn render(&mut self, frame: &mut Frame) -> Result<(), &'static str>;
+
+    /// Called with the logical size of the window, once at the start and after each resize.
+    fn on_resize(&mut self, _width: f64, _height: f64) {}
+
+    /// Only asked in `RedrawMode::OnDemand`, after the updates of a frame and again after its
+    /// render: whether something changed that isn't drawn yet, or an animation is running.
+    /// While it's true the runner keeps polling, otherwise it sleeps until the next input.
+    fn needs_redraw(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedrawMode {
+    /// Poll for input and render every frame, for games and anything animated.
+    Continuous,
+    /// Sleep until there's input or a redraw request and render only when the app asks for
+    /// it, for tools that sit still most of the time.
+    OnDemand,
+}
+
+/// Wakes a `Runner` in `RedrawMode::OnDemand` up for a redraw, from any thread.
+#[derive(Clone)]
+pub struct RedrawRequester {
+    proxy: EventsLoopProxy,
+}
+
+impl RedrawRequester {
+    pub fn request_redraw(&self) {
+        // Fails only once the loop is gone, and then there's nothing to redraw
+        let _ = self.proxy.wakeup();
+    }
+}
+
+/// What `App::render` draws with.
+pub struct Frame<'a> {
+    pub hal_state: &'a mut HalState,
+    pub time: RenderTime,
+    pub stats: &'a FrameStats,
+}
+
+pub struct Runner {
+    /// Until `run` opens the window with it, after `--list-monitors` had its chance to run
+    /// without one.
+    events_loop: Option<EventsLoop>,
+    window_config: WindowConfig,
+    input_map: InputMap,
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+    timestep: FixedTimestep,
+    frame_limiter: FrameLimiter,
+    redraw_mode: RedrawMode,
+    /// Set by `--list-monitors`, `run` only prints them then.
+    list_monitors: bool,
+}
+
+impl Runner {
+    /// For a plain window, with the default bindings.
+    pub fn new<T: Into<String>>(title: T, size: LogicalSize) -> Result<Self, String> {
+        Self::from_config(WindowConfig::new(title, size))
+    }
+
+    /// For the window `config` describes, with the default bindings. It opens once the runner
+    /// runs.
+    pub fn from_config(config: WindowConfig) -> Result<Self, String> {
+        let input_map = InputMap::from_toml(input_map::DEFAULT_BINDINGS)
+            .expect("The default bindings are broken");
+        Ok(Self {
+            events_loop: Some(EventsLoop::new()),
+            window_config: config,
+            input_map,
+            recorder: None,
+            replay: None,
+            timestep: FixedTimestep::new(
+                Duration::from_secs(1) / UPDATE_RATE,
+                MAX_UPDATES_PER_FRAME,
+            ),
+            frame_limiter: FrameLimiter::new(None),
+            redraw_mode: RedrawMode::Continuous,
+            list_monitors: false,
+        })
+    }
+
+    pub fn with_input_map(mut self, input_map: InputMap) -> Self {
+        self.input_map = input_map;
+        self
+    }
+
+    /// Caps the frame rate at `fps`, and at `unfocused_fps` while the window is in the
+    /// background. `None` leaves it to the present mode.
+    pub fn with_frame_limit(mut self, fps: Option<u32>, unfocused_fps: Option<u32>) -> Self {
+        self.frame_limiter.set_fps(fps);
+        self.frame_limiter.set_unfocused_fps(unfocused_fps);
+        self
+    }
+
+    pub fn with_redraw_mode(mut self, redraw_mode: RedrawMode) -> Self {
+        self.redraw_mode = redraw_mode;
+        self
+    }
+
+    pub fn redraw_requester(&self) -> RedrawRequester {
+        RedrawRequester {
+            proxy: self.events_loop().create_proxy(),
+        }
+    }
+
+    /// Records the input of every frame to `path`, see `input_record`.
+    pub fn record_to(mut self, path: &Path) -> Result<Self, String> {
+        self.recorder = Some(Recorder::create(path)?);
+        Ok(self)
+    }
+
+    /// Plays the input recorded in `path` back instead of the real one, and stops at its end.
+    pub fn replay_from(mut self, path: &Path) -> Result<Self, String> {
+        self.replay = Some(Replay::open(path)?);
+        Ok(self)
+    }
+
+    /// Applies the options every app understands, `--bindings <file.toml>`, `--record <file>`,
+    /// `--replay <file>`, `--fps <n>`, `--background-fps <n>`, `--on-demand`, `--monitor <n>` and
+    /// `--list-monitors`, and hands back the other arguments.
+    pub fn with_args<I>(mut self, args: I) -> Result<(Self, Vec<String>), String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut rest = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            let fps = |value: String| {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| format!("`{}` isn't a frame rate", value))
+            };
+            match arg.as_str() {
+                "--bindings" => self.input_map = InputMap::load(Path::new(&value()?))?,
+                "--record" => self = self.record_to(Path::new(&value()?))?,
+                "--replay" => self = self.replay_from(Path::new(&value()?))?,
+                "--fps" => self.frame_limiter.set_fps(Some(fps(value()?)?)),
+                "--background-fps" => self.frame_limiter.set_unfocused_fps(Some(fps(value()?)?)),
+                "--on-demand" => self.redraw_mode = RedrawMode::OnDemand,
+                "--monitor" => {
+                    let value = value()?;
+                    let index = value
+                        .parse()
+                        .map_err(|_| format!("`{}` isn't a monitor number", value))?;
+                    if index >= winit_state::monitors(self.events_loop()).len() {
+                        return Err(format!("There's no monitor {}", index));
+                    }
+                    self.window_config.monitor = Some(index);
+                }
+                "--list-monitors" => self.list_monitors = true,
+                _ => rest.push(arg),
+            }
+        }
+        if self.recorder.is_some() && self.replay.is_some() {
+            return Err("Can't record and replay at the same time".to_owned());
+        }
+        Ok((self, rest))
+    }
+
+    /// Runs `app` until the window closes, the `quit` action fires or the replay ends.
+    pub fn run<A: App>(mut self, app: &mut A) -> Result<(), Box<dyn Error>> {
+        if self.list_monitors {
+            self.print_monitors();
+            return Ok(());
+        }
+        let mut winit_state = self.open_window()?;
+        let mut hal_state = create_hal_state(&winit_state, app)?;
+        let mut input = UserInput::default();
+        let mut window_input = UserInput::default();
+        input.hidpi_factor = winit_state.hidpi_factor();
+        window_input.hidpi_factor = input.hidpi_factor;
+        let mut stats_logged = Instant::now();
+        let on_demand = self.redraw_mode == RedrawMode::OnDemand;
+        // Nothing has been drawn yet
+        let mut animating = true;
+
+        loop {
+            let wait = on_demand && !animating;
+            let resized =
+                match self.next_input(&mut winit_state, &mut input, &mut window_input, wait)? {
+                    Some(resized) => resized,
+                    None => break,
+                };
+            if resized {
+                // Minimizing can leave nothing to make a swapchain for, the next resize retries
+                if let Err(e) = hal_state.recreate_swapchain(winit_state.physical_size()) {
+                    warn!("Couldn't recreate the swapchain: {}", e);
+                }
+                let (width, height) = inner_size(&winit_state);
+                app.on_resize(width, height);
+            }
+
+            app.update(&input, &self.input_map);
+            for update_time in self.timestep.advance(input.frame_time) {
+                app.fixed_update(&update_time);
+            }
+            let redraw = !on_demand
+                || resized
+                || input.redraw_requested
+                || window_input.redraw_requested
+                || app.needs_redraw();
+            if !redraw {
+                animating = false;
+                continue;
+            }
+            let mut frame = Frame {
+                hal_state: &mut hal_state,
+                time: self.timestep.render_time(),
+                stats: self.frame_limiter.stats(),
+            };
+            if let Err(e) = app.render(&mut frame) {
+                error!("{:#?}", e);
+            }
+            animating = app.needs_redraw();
+
+            let focused = !input.unfocused && !window_input.unfocused;
+            self.frame_limiter.wait(focused);
+            if stats_logged.elapsed() >= STATS_INTERVAL {
+                info!("{}", self.frame_limiter.stats());
+                stats_logged = Instant::now();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like `run`, but with `renderer` and the `HalState` on a render thread. `simulation`
+    /// stays on this one with the input and window events, which keep flowing however long a
+    /// frame takes. The frame limit applies to the render thread.
+    pub fn run_threaded<S, R>(
+        mut self,
+        simulation: &mut S,
+        renderer: R,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: Simulation,
+        R: Renderer<Snapshot = S::Snapshot>,
+    {
+        if self.list_monitors {
+            self.print_monitors();
+            return Ok(());
+        }
+        let mut winit_state = self.open_window()?;
+        let frame_limiter = std::mem::replace(&mut self.frame_limiter, FrameLimiter::new(None));
+        let mut render_thread =
+            RenderThread::spawn(winit_state.window.clone(), renderer, frame_limiter);
+        let (width, height) = inner_size(&winit_state);
+        simulation.on_resize(width, height);
+        let mut input = UserInput::default();
+        let mut window_input = UserInput::default();
+        input.hidpi_factor = winit_state.hidpi_factor();
+        window_input.hidpi_factor = input.hidpi_factor;
+        let on_demand = self.redraw_mode == RedrawMode::OnDemand;
+        let mut animating = true;
+
+        loop {
+            let wait = on_demand && !animating;
+            let resized =
+                match self.next_input(&mut winit_state, &mut input, &mut window_input, wait)? {
+                    Some(resized) => resized,
+                    None => break,
+                };
+            if resized {
+                render_thread.resize()?;
+                let (width, height) = inner_size(&winit_state);
+                simulation.on_resize(width, height);
+            }
+
+            simulation.update(&input, &self.input_map);
+            for update_time in self.timestep.advance(input.frame_time) {
+                simulation.fixed_update(&update_time);
+            }
+            let redraw = !on_demand
+                || resized
+                || input.redraw_requested
+                || window_input.redraw_requested
+                || simulation.needs_redraw();
+            if !redraw {
+                animating = false;
+                continue;
+            }
+            let focused = !input.unfocused && !window_input.unfocused;
+            let snapshot = simulation.snapshot();
+            let sent = render_thread.send_frame(snapshot, self.timestep.render_time(), focused)?;
+            // A dropped frame has to be sent again, even if nothing changes in the meantime
+            animating = !sent || simulation.needs_redraw();
+            render_thread.wait_rendered(Duration::from_secs(1) / UPDATE_RATE)?;
+        }
+
+        render_thread.finish()?;
+        Ok(())
+    }
+
+    fn events_loop(&self) -> &EventsLoop {
+        self.events_loop
+            .as_ref()
+            .expect("The window is open already")
+    }
+
+    fn open_window(&mut self) -> Result<WinitState, String> {
+        let events_loop = self.events_loop.take().expect("The window is open already");
+        WinitState::from_config(self.window_config.clone(), events_loop)
+    }
+
+    fn print_monitors(&self) {
+        let monitors = winit_state::monitors(self.events_loop());
+        if monitors.is_empty() {
+            println!("No monitors found");
+        }
+        for monitor in monitors {
+            println!("{}", monitor);
+        }
+    }
+
+    /// Gets the input of the next frame, from the window or the replay, records it and
+    /// switches window modes and monitors. `None` when it's time to stop, otherwise whether
+    /// the window changed size. With `wait` it sleeps until something happens, unless it's
+    /// replaying.
+    fn next_input(
+        &mut self,
+        winit_state: &mut WinitState,
+        input: &mut UserInput,
+        window_input: &mut UserInput,
+        wait: bool,
+    ) -> Result<Option<bool>, String> {
+        let resized = match &mut self.replay {
+            Some(replay) => {
+                // The window still needs its events handled, only closing it counts though
+                window_input.poll_events_loop(&mut winit_state.events_loop);
+                if window_input.end_requested || !replay.next_frame(input)? {
+                    return Ok(None);
+                }
+                window_input.resized() || input.resized()
+            }
+            None if wait => {
+                input.wait_events_loop(&mut winit_state.events_loop);
+                // Time spent asleep isn't simulated, an animation starting now would
+                // otherwise jump ahead
+                input.frame_time = Duration::from_secs(0);
+                input.resized()
+            }
+            None => {
+                input.poll_events_loop(&mut winit_state.events_loop);
+                input.resized()
+            }
+        };
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(input)?;
+        }
+        if input.end_requested || self.input_map.just_activated(input, "quit") {
+            return Ok(None);
+        }
+        let mut resized = resized;
+        for &(action, mode) in &[
+            ("fullscreen", WindowMode::Fullscreen),
+            ("borderless", WindowMode::Borderless),
+        ] {
+            if self.input_map.just_activated(input, action) {
+                winit_state.toggle_mode(mode);
+                // The new size doesn't always come with an event of its own
+                resized = true;
+            }
+        }
+        if self.input_map.just_activated(input, "next_monitor") {
+            match winit_state.move_to_next_monitor() {
+                Ok(moved) => resized |= moved,
+                Err(e) => warn!("{}", e),
+            }
+        }
+        Ok(Some(resized))
+    }
+}
+
+fn create_hal_state<A: App>(winit_state: &WinitState, app: &mut A) -> Result<HalState, String> {
+    let mut hal_state = HalState::new(&winit_state.window, app.shaders())?;
+    app.init(&mut hal_state)?;
+    let (width, height) = inner_size(winit_state);
+    app.on_resize(width, height);
+    Ok(hal_state)
+}
+
+/// The logical size of the window, zero once it's gone.
+fn inner_size(winit_state: &WinitState) -> (f64, f64) {
+    winit_state
+        .logical_size()
+        .map(|logical| logical.into())
+        .unwrap_or((0.0, 0.0))
+}
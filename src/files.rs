@@ -0,0 +1,43 @@
+//! Where files that outlive a run go, and writing them without leaving half of one behind.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The `nicegfx` directory in the user cache directory, for what can be thrown away any time.
+/// `None` when there's no such directory.
+pub fn cache_dir() -> Option<PathBuf> {
+    user_dir("Library/Caches", "XDG_CACHE_HOME", ".cache")
+}
+
+/// The `nicegfx` directory in the user state directory, for what should last between runs but
+/// isn't worth a backup. `None` when there's no such directory.
+pub fn state_dir() -> Option<PathBuf> {
+    user_dir("Library/Application Support", "XDG_STATE_HOME", ".local/state")
+}
+
+/// Replaces `path` with `contents`, creating the directories up to it. They're written next to
+/// it first, so a crash halfway leaves the old file in place.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, contents)?;
+    std::fs::rename(&partial, path)
+}
+
+/// `%LOCALAPPDATA%` on Windows, `macos` under the home directory on macOS, and the XDG
+/// directory in `xdg_var` or `xdg_default` under the home directory everywhere else.
+fn user_dir(macos: &str, xdg_var: &str, xdg_default: &str) -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    let dir = if cfg!(windows) {
+        var("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| Path::new(&home).join(macos))
+    } else {
+        var(xdg_var)
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| Path::new(&home).join(xdg_default)))
+    };
+    dir.map(|dir| dir.join("nicegfx"))
+}
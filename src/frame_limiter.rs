@@ -0,0 +1,165 @@
+//! Caps the frame rate when presenting doesn't, as with the `Immediate` and `Mailbox` present
+//! modes, and keeps statistics of how long frames take.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// `thread::sleep` can oversleep by a millisecond or two, the last stretch is spun instead.
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+/// How many frames the statistics cover.
+const STATS_FRAMES: usize = 240;
+
+/// How often the runner logs the statistics.
+pub(crate) const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct FrameLimiter {
+    /// Frame period, `None` for no limit.
+    target: Option<Duration>,
+    /// Frame period while the window is in the background.
+    unfocused_target: Option<Duration>,
+    deadline: Option<Instant>,
+    last_frame: Option<Instant>,
+    stats: FrameStats,
+}
+
+impl FrameLimiter {
+    /// Limits to `fps` frames a second, or not at all with `None`.
+    pub fn new(fps: Option<u32>) -> Self {
+        Self {
+            target: fps.map(period),
+            unfocused_target: None,
+            deadline: None,
+            last_frame: None,
+            stats: FrameStats::default(),
+        }
+    }
+
+    /// A limit only used while the window doesn't have the focus. The lower of it and the
+    /// main limit wins.
+    pub fn set_unfocused_fps(&mut self, fps: Option<u32>) {
+        self.unfocused_target = fps.map(period);
+    }
+
+    pub fn set_fps(&mut self, fps: Option<u32>) {
+        self.target = fps.map(period);
+    }
+
+    /// Waits until the next frame is due and records how long this one took.
+    pub fn wait(&mut self, focused: bool) {
+        let target = if focused {
+            self.target
+        } else {
+            // The longer period, `None` orders below any
+            self.target.max(self.unfocused_target)
+        };
+        if let Some(target) = target {
+            let now = Instant::now();
+            let deadline = match self.deadline {
+                // Frames that ran late don't get made up for with a burst of short ones
+                Some(deadline) if deadline + target > now => deadline + target,
+                _ => now + target,
+            };
+            sleep_until(deadline);
+            self.deadline = Some(deadline);
+        } else {
+            self.deadline = None;
+        }
+
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.stats.push(now - last_frame);
+        }
+        self.last_frame = Some(now);
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+}
+
+/// Frame times over the last few seconds worth of frames.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    frame_times: VecDeque<Duration>,
+}
+
+impl FrameStats {
+    pub fn push(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == STATS_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.frame_times.is_empty() {
+            return Duration::from_secs(0);
+        }
+        self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32
+    }
+
+    pub fn min(&self) -> Duration {
+        self.frame_times.iter().min().cloned().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.frame_times.iter().max().cloned().unwrap_or_default()
+    }
+
+    /// Standard deviation of the frame times, close to zero when pacing is stable.
+    pub fn jitter(&self) -> Duration {
+        if self.frame_times.is_empty() {
+            return Duration::from_secs(0);
+        }
+        let mean = seconds(self.mean());
+        let variance = self
+            .frame_times
+            .iter()
+            .map(|&frame_time| (seconds(frame_time) - mean).powi(2))
+            .sum::<f64>()
+            / self.frame_times.len() as f64;
+        Duration::from_nanos((variance.sqrt() * 1e9) as u64)
+    }
+
+    pub fn fps(&self) -> f64 {
+        let mean = seconds(self.mean());
+        if mean > 0.0 {
+            1.0 / mean
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} fps, frame times {:?} to {:?}, jitter {:?}",
+            self.fps(),
+            self.min(),
+            self.max(),
+            self.jitter()
+        )
+    }
+}
+
+fn period(fps: u32) -> Duration {
+    Duration::from_secs(1) / fps.max(1)
+}
+
+fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now + SPIN_MARGIN {
+        std::thread::sleep(deadline - now - SPIN_MARGIN);
+    }
+    while Instant::now() < deadline {
+        std::thread::yield_now();
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) * 1e-9
+}
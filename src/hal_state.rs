@@ -7,12 +7,16 @@ use gfx_hal::{adapter::PhysicalDevice,
               pass::{Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, Subpass,
                      SubpassDependency, SubpassDesc, SubpassRef},
               pool::CommandPoolCreateFlags,
-              pso::{BlendState, ColorBlendDesc, ColorMask, EntryPoint, GraphicsPipelineDesc,
-                    GraphicsShaderSet, PipelineStage, Rasterizer, Rect, Viewport},
+              pso::{BakedStates, BasePipeline, BlendDesc, BlendState, ColorBlendDesc, ColorMask,
+                    DepthStencilDesc, DepthTest, Descriptor, DescriptorPool, DescriptorRangeDesc,
+                    DescriptorSetWrite, DescriptorType, EntryPoint, GraphicsPipelineDesc,
+                    GraphicsShaderSet, InputAssemblerDesc, LogicOp, PipelineCreationFlags,
+                    PipelineStage, Rasterizer, Rect, Specialization, StencilTest, Viewport},
               queue::family::QueueFamily,
               queue::Submission,
+              window::Extent2D,
               window::Surface,
-              window::{Extent2D, PresentMode},
+              window::PresentMode,
               Adapter,
               Backbuffer,
               Backend,
@@ -32,11 +36,23 @@ use gfx_hal::{adapter::PhysicalDevice,
 use log::{debug, error, info, trace, warn};
 use std::error::Error;
 
+use winit::dpi::PhysicalSize;
 use winit::Window;
 
 use arrayvec::ArrayVec;
 
+use image::{Rgba, RgbaImage};
+
+use crate::pipeline_cache;
+use crate::shaders::{self, ShaderPair, SpecializationKey};
+use crate::texture::LoadedImage;
+use crate::winit_state;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::mem::ManuallyDrop;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::ptr::read;
 
 #[cfg(feature = "dx12")]
@@ -56,12 +72,31 @@ pub struct HalState {
     image_available_semaphores: Vec<<back::Backend as Backend>::Semaphore>,
     command_buffers: Vec<CommandBuffer<back::Backend, Graphics, MultiShot, Primary>>,
     command_pool: ManuallyDrop<CommandPool<back::Backend, Graphics>>,
+    /// Every specialization built so far, they all go when the shaders change. The viewport and
+    /// scissor are dynamic, so they outlive the swapchain.
+    pipelines: HashMap<SpecializationKey, <back::Backend as Backend>::GraphicsPipeline>,
+    specialization: SpecializationKey,
+    pipeline_cache: ManuallyDrop<<back::Backend as Backend>::PipelineCache>,
+    /// Where the pipeline cache gets saved on drop.
+    pipeline_cache_path: Option<PathBuf>,
+    shader_pair: ShaderPair,
+    vertex_spirv: Cow<'static, [u8]>,
+    fragment_spirv: Cow<'static, [u8]>,
+    /// Keyed by `(set, binding)`.
+    textures: Vec<((u32, u32), LoadedImage<back::Backend>)>,
+    descriptor_sets: Vec<<back::Backend as Backend>::DescriptorSet>,
+    descriptor_pool: Option<<back::Backend as Backend>::DescriptorPool>,
+    pipeline_layout: ManuallyDrop<<back::Backend as Backend>::PipelineLayout>,
+    descriptor_set_layouts: Vec<<back::Backend as Backend>::DescriptorSetLayout>,
     framebuffers: Vec<<back::Backend as Backend>::Framebuffer>,
     image_views: Vec<(<back::Backend as Backend>::ImageView)>,
     render_pass: ManuallyDrop<<back::Backend as Backend>::RenderPass>,
     pub render_area: Rect,
     queue_group: QueueGroup<back::Backend, Graphics>,
-    swapchain: ManuallyDrop<<back::Backend as Backend>::Swapchain>,
+    /// `None` only after `recreate_swapchain` failed.
+    swapchain: Option<<back::Backend as Backend>::Swapchain>,
+    /// What the swapchain was last created with, recreating it only changes the extent.
+    swapchain_config: SwapchainConfig,
     device: ManuallyDrop<back::Device>,
     _adapter: Adapter<back::Backend>,
     _surface: <back::Backend as Backend>::Surface,
@@ -69,7 +104,30 @@ pub struct HalState {
 }
 
 impl HalState {
-    pub fn new(window: &Window) -> Result<Self, &'static str> {
+    /// Sets up everything needed to draw `shader_pair` into `window`. Every texture the shaders
+    /// sample starts out as a single black pixel, see `set_texture`.
+    pub fn new(window: &Window, shader_pair: ShaderPair) -> Result<Self, &'static str> {
+        let vertex_spirv =
+            shaders::spirv(shader_pair.vertex.name).ok_or("Missing the vertex shader")?;
+        let fragment_spirv =
+            shaders::spirv(shader_pair.fragment.name).ok_or("Missing the fragment shader")?;
+        Self::with_spirv(window, shader_pair, vertex_spirv, fragment_spirv)
+    }
+
+    /// Like `new`, with SPIR-V from somewhere other than the build, like shaders the app
+    /// compiled or loaded itself. It has to match the interfaces of `shader_pair`.
+    pub fn with_spirv<V, F>(
+        window: &Window,
+        shader_pair: ShaderPair,
+        vertex_spirv: V,
+        fragment_spirv: F,
+    ) -> Result<Self, &'static str>
+    where
+        V: Into<Cow<'static, [u8]>>,
+        F: Into<Cow<'static, [u8]>>,
+    {
+        let vertex_spirv = vertex_spirv.into();
+        let fragment_spirv = fragment_spirv.into();
         let instance = back::Instance::create(WINDOW_NAME, 1);
         let mut surface = instance.create_surface(window);
         let adapter = instance
@@ -106,7 +164,7 @@ impl HalState {
             (device, queue_group)
         };
 
-        let (swapchain, extent, backbuffer, format, frames_in_flight) = {
+        let swapchain_config = {
             let (caps, preferred_formats, present_modes, composite_alphas) =
                 surface.compatibility(&adapter.physical_device);
             warn!("{:?}", caps);
@@ -144,7 +202,7 @@ impl HalState {
                         .ok_or("Preffered format list was empty")?,
                 },
             };
-            let extent = caps.extents.end;
+            let extent = swapchain_extent(winit_state::physical_size(window), &caps.extents);
             let image_count = if present_mode == PresentMode::Mailbox {
                 (caps.image_count.end - 1).min(3)
             } else {
@@ -156,7 +214,7 @@ impl HalState {
             } else {
                 Err("The surfade isn't capable of supporting color")?
             };
-            let swapchain_config = SwapchainConfig {
+            SwapchainConfig {
                 present_mode,
                 composite_alpha,
                 format,
@@ -164,16 +222,11 @@ impl HalState {
                 image_count,
                 image_layers,
                 image_usage,
-            };
-            info!("{:?}", swapchain_config);
-            //
-            let (swapchain, backbuffer) = unsafe {
-                device
-                    .create_swapchain(&mut surface, swapchain_config, None)
-                    .map_err(|_| "Failed to create the swapchain")?
-            };
-            (swapchain, extent, backbuffer, format, image_count as usize)
+            }
         };
+        info!("{:?}", swapchain_config);
+        let format = swapchain_config.format;
+        let frames_in_flight = swapchain_config.image_count as usize;
 
         let (image_available_semaphores, render_finished_semaphores, in_flight_fences) = {
             let mut image_available_semaphores: Vec<
@@ -226,46 +279,8 @@ impl HalState {
             }
         };
 
-        let image_views: Vec<_> = match backbuffer {
-            Backbuffer::Images(images) => images
-                .into_iter()
-                .map(|image| unsafe {
-                    device
-                        .create_image_view(
-                            &image,
-                            ViewKind::D2,
-                            format,
-                            Swizzle::NO,
-                            SubresourceRange {
-                                aspects: Aspects::COLOR,
-                                levels: 0..1,
-                                layers: 0..1,
-                            },
-                        )
-                        .map_err(|_| "Couldn't create the image_view for the image")
-                })
-                .collect::<Result<Vec<_>, &str>>()?,
-            Backbuffer::Framebuffer(_) => unimplemented!("Can't handle framebuffer backbuffer"),
-        };
-
-        let framebuffers: Vec<<back::Backend as Backend>::Framebuffer> = {
-            image_views
-                .iter()
-                .map(|image_view| unsafe {
-                    device
-                        .create_framebuffer(
-                            &render_pass,
-                            vec![image_view],
-                            Extent {
-                                width: extent.width as u32,
-                                height: extent.height as u32,
-                                depth: 1,
-                            },
-                        )
-                        .map_err(|_| "Failed to create a framebuffer")
-                })
-                .collect::<Result<Vec<_>, &str>>()?
-        };
+        let (swapchain, image_views, framebuffers) =
+            Self::create_swapchain(&device, &mut surface, &render_pass, swapchain_config.clone())?;
 
         let mut command_pool = unsafe {
             device
@@ -278,27 +293,105 @@ impl HalState {
             .map(|_| command_pool.acquire_command_buffer())
             .collect();
 
-        Ok(Self {
+        let (descriptor_set_layouts, pipeline_layout) =
+            Self::create_pipeline_layout(&device, shader_pair)?;
+        let (descriptor_pool, descriptor_sets) =
+            Self::create_descriptor_sets(&device, shader_pair, &descriptor_set_layouts)?;
+        let pipeline_cache_path = pipeline_cache::path();
+        let pipeline_cache = unsafe {
+            // The driver can still turn the data down, then it starts over empty
+            let loaded = pipeline_cache_path
+                .as_ref()
+                .and_then(|path| pipeline_cache::load(path, &adapter.info))
+                .and_then(|data| device.create_pipeline_cache(Some(&data[..])).ok());
+            match loaded {
+                Some(pipeline_cache) => pipeline_cache,
+                None => device
+                    .create_pipeline_cache(None)
+                    .map_err(|_| "Couldn't create the pipeline cache")?,
+            }
+        };
+        let mut hal_state = Self {
             _instance: ManuallyDrop::new(instance),
             _surface: surface,
             _adapter: adapter,
             device: ManuallyDrop::new(device),
             queue_group,
-            swapchain: ManuallyDrop::new(swapchain),
-            render_area: extent.to_extent().rect(),
+            swapchain: Some(swapchain),
+            render_area: swapchain_config.extent.to_extent().rect(),
+            swapchain_config,
             render_pass: ManuallyDrop::new(render_pass),
             image_views,
             framebuffers,
             command_pool: ManuallyDrop::new(command_pool),
             command_buffers,
+            descriptor_set_layouts,
+            pipeline_layout: ManuallyDrop::new(pipeline_layout),
+            pipelines: HashMap::new(),
+            specialization: SpecializationKey::default(),
+            pipeline_cache: ManuallyDrop::new(pipeline_cache),
+            pipeline_cache_path,
+            shader_pair,
+            vertex_spirv,
+            fragment_spirv,
+            textures: vec![],
+            descriptor_sets,
+            descriptor_pool,
             image_available_semaphores,
             render_finished_semaphores,
             in_flight_fences,
             frames_in_flight,
             current_frame: 0,
-        })
+        };
+        hal_state.specialize(SpecializationKey::default())?;
+
+        let placeholder = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        for stage in &shader_pair.stages() {
+            for binding in stage.descriptor_bindings {
+                if binding.ty == DescriptorType::CombinedImageSampler {
+                    hal_state.set_texture((binding.set, binding.binding), &placeholder)?;
+                }
+            }
+        }
+        Ok(hal_state)
     }
     pub fn draw_clear_frame(&mut self, color: [f32; 4]) -> Result<(), &'static str> {
+        self.draw_frame(color, None)
+    }
+    /// Clears to `color`, then draws `vertex_count` vertices with the pipeline, which get their
+    /// positions from the vertex shader. `push_constants` start at offset 0, each stage gets
+    /// the part its block covers. `shaders::as_words` turns the generated `PushConstants` into
+    /// them.
+    pub fn draw_vertices_frame(
+        &mut self,
+        color: [f32; 4],
+        vertex_count: u32,
+        push_constants: &[u32],
+    ) -> Result<(), &'static str> {
+        self.draw_frame(color, Some((vertex_count, push_constants)))
+    }
+    /// Clears to `color`, then draws `(vertex_count, push_constants)` with the pipeline if
+    /// there's something to draw.
+    fn draw_frame(
+        &mut self,
+        color: [f32; 4],
+        draw: Option<(u32, &[u32])>,
+    ) -> Result<(), &'static str> {
+        // Recreating it failed, there's nothing to draw into until the next resize
+        let swapchain = match self.swapchain.as_mut() {
+            Some(swapchain) => swapchain,
+            None => return Ok(()),
+        };
+        // Looked up before the fence or the swapchain get touched, so a missing one leaves no mess
+        let pipeline = match draw {
+            Some(_) => Some(
+                self.pipelines
+                    .get(&self.specialization)
+                    .ok_or("Couldn't find the pipeline of the current specialization!")?,
+            ),
+            None => None,
+        };
+
         // SETUP FOR THIS FRAME
         let flight_fence = &self.in_flight_fences[self.current_frame];
         let image_available = &self.image_available_semaphores[self.current_frame];
@@ -313,7 +406,7 @@ impl HalState {
             self.device
                 .reset_fence(flight_fence)
                 .map_err(|_| "Couldn't reset the fence!")?;
-            let image_index = self.swapchain
+            let image_index = swapchain
                 .acquire_image(core::u64::MAX, FrameSync::Semaphore(image_available))
                 .map_err(|_| "Couldn't acquire an image from the swapchain!")?;
             (image_index, image_index as usize)
@@ -324,12 +417,48 @@ impl HalState {
             let buffer = &mut self.command_buffers[i_usize];
             let clear_values = [ClearValue::Color(ClearColor::Float(color))];
             buffer.begin(false);
-            buffer.begin_render_pass_inline(
-                &self.render_pass,
-                &self.framebuffers[i_usize],
-                self.render_area,
-                clear_values.iter(),
-            );
+            {
+                let mut encoder = buffer.begin_render_pass_inline(
+                    &self.render_pass,
+                    &self.framebuffers[i_usize],
+                    self.render_area,
+                    clear_values.iter(),
+                );
+                if let (Some((vertex_count, push_constants)), Some(pipeline)) = (draw, pipeline) {
+                    encoder.bind_graphics_pipeline(pipeline);
+                    encoder.set_viewports(
+                        0,
+                        &[Viewport {
+                            rect: self.render_area,
+                            depth: (0.0..1.0),
+                        }],
+                    );
+                    encoder.set_scissors(0, &[self.render_area]);
+                    if !self.descriptor_sets.is_empty() {
+                        encoder.bind_graphics_descriptor_sets(
+                            &self.pipeline_layout,
+                            0,
+                            &self.descriptor_sets,
+                            &[],
+                        );
+                    }
+                    let updates = shaders::push_constant_updates(&self.shader_pair.stages());
+                    for (stages, range) in updates {
+                        // Blocks start and end on whole words, and what wasn't given isn't pushed
+                        let end = (range.end as usize / 4).min(push_constants.len());
+                        let start = range.start as usize / 4;
+                        if start < end {
+                            encoder.push_graphics_constants(
+                                &self.pipeline_layout,
+                                stages,
+                                range.start,
+                                &push_constants[start..end],
+                            );
+                        }
+                    }
+                    encoder.draw(0..vertex_count, 0..1);
+                }
+            }
             buffer.finish();
         }
 
@@ -348,117 +477,370 @@ impl HalState {
         let the_command_queue = &mut self.queue_group.queues[0];
         unsafe {
             the_command_queue.submit(submission, Some(flight_fence));
-            self.swapchain
+            swapchain
                 .present(the_command_queue, i_u32, present_wait_semaphores)
                 .map_err(|_| "Failed to present into the swapchain!")
         }
     }
-    pub fn recreate_swapchain(&mut self, window: &Window) -> Result<(), &'static str> {
-        self.cleanup_swapchain();
+    /// Rebuilds the swapchain and its framebuffers for a window that's `size` big now, see
+    /// `WinitState::physical_size`. Everything else stays, the device, the textures, the shaders
+    /// and the pipelines along with it. If that fails, frames get skipped until a later call
+    /// succeeds.
+    pub fn recreate_swapchain(&mut self, size: Option<PhysicalSize>) -> Result<(), &'static str> {
+        // Frames in flight may still be drawing into the old images
+        let _ = self.device.wait_idle();
+        self.destroy_swapchain();
 
+        let (caps, _, _, _) = self._surface.compatibility(&self._adapter.physical_device);
+        let swapchain_config = SwapchainConfig {
+            extent: swapchain_extent(size, &caps.extents),
+            ..self.swapchain_config.clone()
+        };
+        let (swapchain, image_views, framebuffers) = Self::create_swapchain(
+            &self.device,
+            &mut self._surface,
+            &self.render_pass,
+            swapchain_config.clone(),
+        )?;
+        // The driver can hand out more images than asked for
+        while self.command_buffers.len() < framebuffers.len() {
+            self.command_buffers
+                .push(self.command_pool.acquire_command_buffer());
+        }
+        self.swapchain = Some(swapchain);
+        self.image_views = image_views;
+        self.framebuffers = framebuffers;
+        self.render_area = swapchain_config.extent.to_extent().rect();
+        self.swapchain_config = swapchain_config;
+        Ok(())
+    }
+    /// Draws with the pipeline specialized for `key` from now on. It gets built the first time
+    /// the key comes up and stays cached after that.
+    pub fn specialize(&mut self, key: SpecializationKey) -> Result<(), &'static str> {
+        if !self.pipelines.contains_key(&key) {
+            let graphics_pipeline =
+                self.create_pipeline(&key, &self.vertex_spirv, &self.fragment_spirv)?;
+            self.pipelines.insert(key.clone(), graphics_pipeline);
+        }
+        self.specialization = key;
+        Ok(())
+    }
+    /// Rebuilds the pipeline from freshly compiled shaders, `None` keeping the current one of
+    /// that stage. If that fails the current pipeline stays, so a broken shader doesn't take
+    /// the window down with it.
+    pub fn replace_shaders(
+        &mut self,
+        vertex_spirv: Option<Vec<u8>>,
+        fragment_spirv: Option<Vec<u8>>,
+    ) -> Result<(), &'static str> {
+        let vertex_spirv = vertex_spirv.map_or_else(|| self.vertex_spirv.clone(), Cow::Owned);
+        let fragment_spirv =
+            fragment_spirv.map_or_else(|| self.fragment_spirv.clone(), Cow::Owned);
+        let graphics_pipeline =
+            self.create_pipeline(&self.specialization, &vertex_spirv, &fragment_spirv)?;
+
+        // Frames in flight may still be using the old pipelines
+        let _ = self.device.wait_idle();
+        self.destroy_pipelines();
+        self.pipelines
+            .insert(self.specialization.clone(), graphics_pipeline);
+        self.vertex_spirv = vertex_spirv;
+        self.fragment_spirv = fragment_spirv;
+        Ok(())
+    }
+    /// Uploads `image` and points the combined image sampler at `(set, binding)` to it, every
+    /// element of it for an array of them.
+    pub fn set_texture(
+        &mut self,
+        (set, binding): (u32, u32),
+        image: &RgbaImage,
+    ) -> Result<(), &'static str> {
+        // Anything else there would be undefined behaviour instead of an error
+        let count = shaders::sampler_binding(&self.shader_pair.stages(), (set, binding))?.count;
+        let descriptor_set = self
+            .descriptor_sets
+            .get(set as usize)
+            .ok_or("The shaders don't have that descriptor set")?;
+        let texture = LoadedImage::new(
+            &self._adapter,
+            &self.device,
+            &mut self.command_pool,
+            &mut self.queue_group.queues[0],
+            image,
+        )?;
+
+        // The descriptor can't change while a frame in flight uses it
+        let _ = self.device.wait_idle();
         unsafe {
-            self.device.reset_fences(&self.in_flight_fences[self.current_frame..self.current_frame]).unwrap();
+            self.device.write_descriptor_sets(vec![DescriptorSetWrite {
+                set: descriptor_set,
+                binding,
+                array_offset: 0,
+                descriptors: (0..count).map(|_| {
+                    Descriptor::CombinedImageSampler(
+                        &*texture.image_view,
+                        Layout::ShaderReadOnlyOptimal,
+                        &*texture.sampler,
+                    )
+                }),
+            }]);
         }
+        match self.textures.iter_mut().find(|(key, _)| *key == (set, binding)) {
+            Some((_, old_texture)) => unsafe {
+                std::mem::replace(old_texture, texture).manually_drop(&self.device);
+            },
+            None => self.textures.push(((set, binding), texture)),
+        }
+        Ok(())
+    }
+}
 
-        let (swapchain, extent, backbuffer, format, frames_in_flight) = {
-            let (caps, preferred_formats, present_modes, composite_alphas) =
-                self._surface.compatibility(&self._adapter.physical_device);
-            info!("{:?}", caps);
-            info!("Preferred Formats: {:?}", preferred_formats);
-            info!("Present Modes: {:?}", present_modes);
-            info!("Composite Alphas: {:?}", composite_alphas);
-            //
-            let present_mode = {
-                use gfx_hal::window::PresentMode::*;
-                [Mailbox, Fifo, Relaxed, Immediate]
-                    .iter()
-                    .cloned()
-                    .find(|pm| present_modes.contains(pm))
-                    .ok_or("No PresentMode values specified!")?
-            };
-            let composite_alpha = {
-                use gfx_hal::window::CompositeAlpha::*;
-                [Opaque, Inherit, PreMultiplied, PostMultiplied]
-                    .iter()
-                    .cloned()
-                    .find(|ca| composite_alphas.contains(ca))
-                    .ok_or("No CompositeAlpha values specified")?
-            };
-            let format = match preferred_formats {
-                None => Format::Rgba8Srgb,
-                Some(formats) => match formats
-                    .iter()
-                    .find(|format| format.base_format().1 == ChannelType::Srgb)
-                    .cloned()
-                {
-                    Some(srgb_format) => srgb_format,
-                    None => formats
-                        .get(0)
-                        .cloned()
-                        .ok_or("Preffered format list was empty")?,
-                },
-            };
-            let extent = dbg!(caps.extents.end);
-            let image_count = if present_mode == PresentMode::Mailbox {
-                (caps.image_count.end - 1).min(3)
-            } else {
-                (caps.image_count.end - 1).min(2)
-            };
-            let image_layers = 1;
-            let image_usage = if caps.usage.contains(Usage::COLOR_ATTACHMENT) {
-                Usage::COLOR_ATTACHMENT
-            } else {
-                Err("The surfade isn't capable of supporting color")?
-            };
-            let swapchain_config = SwapchainConfig {
-                present_mode,
-                composite_alpha,
-                format,
-                extent,
-                image_count,
-                image_layers,
-                image_usage,
-            };
-            info!("{:?}", swapchain_config);
-            //
-            let (swapchain, backbuffer) = unsafe {
-                self.device
-                    .create_swapchain(&mut self._surface, swapchain_config, None)
-                    .map_err(|_| "Failed to create the swapchain")?
-            };
-            (swapchain, extent, backbuffer, format, image_count as usize)
+impl Drop for HalState {
+    fn drop(&mut self) {
+        let _ = self.device.wait_idle();
+
+        self.destroy_swapchain();
+        self.destroy_pipelines();
+        self.save_pipeline_cache();
+
+        unsafe {
+            self.command_pool.reset();
+            self.device
+                .destroy_render_pass(ManuallyDrop::into_inner(read(&mut self.render_pass)));
+            for fence in self.in_flight_fences.drain(..) {
+                self.device.destroy_fence(fence)
+            }
+            for semaphore in self.image_available_semaphores.drain(..) {
+                self.device.destroy_semaphore(semaphore)
+            }
+            for semaphore in self.render_finished_semaphores.drain(..) {
+                self.device.destroy_semaphore(semaphore)
+            }
+            for (_, texture) in self.textures.drain(..) {
+                texture.manually_drop(&self.device);
+            }
+            // Frees the descriptor sets along with it
+            self.descriptor_sets.clear();
+            if let Some(descriptor_pool) = self.descriptor_pool.take() {
+                self.device.destroy_descriptor_pool(descriptor_pool);
+            }
+            self.device
+                .destroy_pipeline_cache(ManuallyDrop::into_inner(read(&mut self.pipeline_cache)));
+            self.device
+                .destroy_pipeline_layout(ManuallyDrop::into_inner(read(&mut self.pipeline_layout)));
+            for descriptor_set_layout in self.descriptor_set_layouts.drain(..) {
+                self.device.destroy_descriptor_set_layout(descriptor_set_layout)
+            }
+            self.device.destroy_command_pool(
+                ManuallyDrop::into_inner(read(&mut self.command_pool)).into_raw(),
+            );
+
+            ManuallyDrop::drop(&mut self.device);
+            ManuallyDrop::drop(&mut self._instance);
+        }
+    }
+}
+
+impl HalState {
+    /// Creates the descriptor set and pipeline layouts of `shader_pair`, taken from the reflected
+    /// shaders.
+    fn create_pipeline_layout(
+        device: &back::Device,
+        shader_pair: ShaderPair,
+    ) -> Result<
+        (
+            Vec<<back::Backend as Backend>::DescriptorSetLayout>,
+            <back::Backend as Backend>::PipelineLayout,
+        ),
+        &'static str,
+    > {
+        let stages = shader_pair.stages();
+
+        let descriptor_set_layouts = shaders::set_layout_bindings(&stages)
+            .map_err(|e| {
+                error!("{}", e);
+                "The shaders declare a shared descriptor binding differently"
+            })?
+            .into_iter()
+            .map(|bindings| unsafe {
+                device
+                    .create_descriptor_set_layout(bindings, &[])
+                    .map_err(|_| "Couldn't create a descriptor set layout")
+            })
+            .collect::<Result<Vec<_>, &str>>()?;
+        let pipeline_layout = unsafe {
+            device
+                .create_pipeline_layout(
+                    &descriptor_set_layouts,
+                    shaders::push_constant_ranges(&stages),
+                )
+                .map_err(|_| "Couldn't create a pipeline layout")?
         };
+        Ok((descriptor_set_layouts, pipeline_layout))
+    }
 
-        let render_pass = {
-            let color_attachment = Attachment {
-                format: Some(format),
-                samples: 1,
-                ops: AttachmentOps {
-                    load: AttachmentLoadOp::Clear,
-                    store: AttachmentStoreOp::Store,
+    /// One descriptor set per layout, out of a pool just big enough for them.
+    fn create_descriptor_sets(
+        device: &back::Device,
+        shader_pair: ShaderPair,
+        descriptor_set_layouts: &[<back::Backend as Backend>::DescriptorSetLayout],
+    ) -> Result<
+        (
+            Option<<back::Backend as Backend>::DescriptorPool>,
+            Vec<<back::Backend as Backend>::DescriptorSet>,
+        ),
+        &'static str,
+    > {
+        if descriptor_set_layouts.is_empty() {
+            return Ok((None, vec![]));
+        }
+        let descriptor_ranges: Vec<DescriptorRangeDesc> = shader_pair
+            .stages()
+            .iter()
+            .flat_map(|stage| stage.descriptor_bindings)
+            .map(|binding| DescriptorRangeDesc {
+                ty: binding.ty,
+                count: binding.count,
+            })
+            .collect();
+        let mut descriptor_pool = unsafe {
+            device
+                .create_descriptor_pool(descriptor_set_layouts.len(), descriptor_ranges)
+                .map_err(|_| "Couldn't create a descriptor pool")?
+        };
+        let descriptor_sets = descriptor_set_layouts
+            .iter()
+            .map(|layout| unsafe { descriptor_pool.allocate_set(layout) })
+            .collect::<Result<Vec<_>, _>>();
+        match descriptor_sets {
+            Ok(descriptor_sets) => Ok((Some(descriptor_pool), descriptor_sets)),
+            Err(_) => {
+                unsafe { device.destroy_descriptor_pool(descriptor_pool) };
+                Err("Couldn't allocate a descriptor set")
+            }
+        }
+    }
+
+    /// Builds the pipeline drawing the shaders of this state out of the given SPIR-V, for the
+    /// render pass. The viewport and scissor get set when drawing.
+    fn create_pipeline(
+        &self,
+        specialization: &SpecializationKey,
+        vertex_spirv: &[u8],
+        fragment_spirv: &[u8],
+    ) -> Result<<back::Backend as Backend>::GraphicsPipeline, &'static str> {
+        let device = &*self.device;
+        // Vertices come from the vertex shader alone, nothing binds a vertex buffer yet
+        if !self.shader_pair.vertex.inputs.is_empty() {
+            return Err("The vertex shader has inputs, but there's no vertex buffer to feed them");
+        }
+
+        let vertex_module = unsafe {
+            device
+                .create_shader_module(vertex_spirv)
+                .map_err(|_| "Couldn't make the vertex module")?
+        };
+        let fragment_module = match unsafe { device.create_shader_module(fragment_spirv) } {
+            Ok(fragment_module) => fragment_module,
+            Err(_) => {
+                unsafe { device.destroy_shader_module(vertex_module) };
+                return Err("Couldn't make the fragment module");
+            }
+        };
+
+        let (vertex_constants, vertex_data) = specialization.vertex.specialization();
+        let (fragment_constants, fragment_data) = specialization.fragment.specialization();
+        let graphics_pipeline = {
+            let shaders = GraphicsShaderSet {
+                vertex: EntryPoint {
+                    entry: "main",
+                    module: &vertex_module,
+                    specialization: Specialization {
+                        constants: &vertex_constants,
+                        data: &vertex_data,
+                    },
                 },
-                stencil_ops: AttachmentOps::DONT_CARE,
-                layouts: Layout::Undefined..Layout::Present,
+                hull: None,
+                domain: None,
+                geometry: None,
+                fragment: Some(EntryPoint {
+                    entry: "main",
+                    module: &fragment_module,
+                    specialization: Specialization {
+                        constants: &fragment_constants,
+                        data: &fragment_data,
+                    },
+                }),
             };
-            let subpass = SubpassDesc {
-                colors: &[(0, Layout::ColorAttachmentOptimal)],
-                depth_stencil: None,
-                inputs: &[],
-                resolves: &[],
-                preserves: &[],
+            let desc = GraphicsPipelineDesc {
+                shaders,
+                rasterizer: Rasterizer::FILL,
+                vertex_buffers: vec![],
+                attributes: vec![],
+                input_assembler: InputAssemblerDesc::new(Primitive::TriangleList),
+                blender: BlendDesc {
+                    logic_op: Some(LogicOp::Copy),
+                    targets: vec![ColorBlendDesc(ColorMask::ALL, BlendState::ALPHA)],
+                },
+                depth_stencil: DepthStencilDesc {
+                    depth: DepthTest::Off,
+                    depth_bounds: false,
+                    stencil: StencilTest::Off,
+                },
+                multisampling: None,
+                // Left dynamic, see `draw_frame`
+                baked_states: BakedStates {
+                    viewport: None,
+                    scissor: None,
+                    blend_color: None,
+                    depth_bounds: None,
+                },
+                layout: &self.pipeline_layout,
+                subpass: Subpass {
+                    index: 0,
+                    main_pass: &self.render_pass,
+                },
+                flags: PipelineCreationFlags::empty(),
+                parent: BasePipeline::None,
             };
-            unsafe {
-                self.device
-                    .create_render_pass(&[color_attachment], &[subpass], &[])
-                    .map_err(|_| "Couldn't create a render pass")?
-            }
+            unsafe { device.create_graphics_pipeline(&desc, Some(&self.pipeline_cache)) }
+        };
+
+        unsafe {
+            device.destroy_shader_module(vertex_module);
+            device.destroy_shader_module(fragment_module);
+        }
+        graphics_pipeline.map_err(|_| "Couldn't create a graphics pipeline!")
+    }
+
+    /// Creates a swapchain on `surface` as `config` says, and a framebuffer for `render_pass`
+    /// around each of its images.
+    fn create_swapchain(
+        device: &back::Device,
+        surface: &mut <back::Backend as Backend>::Surface,
+        render_pass: &<back::Backend as Backend>::RenderPass,
+        config: SwapchainConfig,
+    ) -> Result<
+        (
+            <back::Backend as Backend>::Swapchain,
+            Vec<<back::Backend as Backend>::ImageView>,
+            Vec<<back::Backend as Backend>::Framebuffer>,
+        ),
+        &'static str,
+    > {
+        let format = config.format;
+        let extent = config.extent;
+        let (swapchain, backbuffer) = unsafe {
+            device
+                .create_swapchain(surface, config, None)
+                .map_err(|_| "Failed to create the swapchain")?
         };
 
         let image_views: Vec<_> = match backbuffer {
             Backbuffer::Images(images) => images
                 .into_iter()
                 .map(|image| unsafe {
-                    self.device
+                    device
                         .create_image_view(
                             &image,
                             ViewKind::D2,
@@ -480,9 +862,9 @@ impl HalState {
             image_views
                 .iter()
                 .map(|image_view| unsafe {
-                    self.device
+                    device
                         .create_framebuffer(
-                            &render_pass,
+                            render_pass,
                             vec![image_view],
                             Extent {
                                 width: extent.width as u32,
@@ -494,66 +876,60 @@ impl HalState {
                 })
                 .collect::<Result<Vec<_>, &str>>()?
         };
-
-
-        self.swapchain = ManuallyDrop::new(swapchain);
-        self.render_area = extent.to_extent().rect();
-        self.render_pass = ManuallyDrop::new(render_pass);
-        self.image_views = image_views;
-        self.framebuffers = framebuffers;
-        self.frames_in_flight = frames_in_flight;
-        self.current_frame = (self.current_frame + 1) % self.frames_in_flight;
-        Ok(())
-    }
-}
-
-impl Drop for HalState {
-    fn drop(&mut self) {
-        let _ = self.device.wait_idle();
-
-        self.cleanup_swapchain();
-
-        unsafe {
-            for fence in self.in_flight_fences.drain(..) {
-                self.device.destroy_fence(fence)
-            }
-            for semaphore in self.image_available_semaphores.drain(..) {
-                self.device.destroy_semaphore(semaphore)
-            }
-            for semaphore in self.render_finished_semaphores.drain(..) {
-                self.device.destroy_semaphore(semaphore)
-            }
-            self.device.destroy_command_pool(
-                ManuallyDrop::into_inner(read(&mut self.command_pool)).into_raw(),
-            );
-
-            ManuallyDrop::drop(&mut self.device);
-            ManuallyDrop::drop(&mut self._instance);
-        }
+        Ok((swapchain, image_views, framebuffers))
     }
-}
 
-impl HalState {
-    fn cleanup_swapchain(&mut self) {
-        let _ = self.device.wait_idle();
+    /// Destroys the swapchain and the framebuffers around its images. The device has to be
+    /// idle.
+    fn destroy_swapchain(&mut self) {
         unsafe {
             for framebuffer in self.framebuffers.drain(..) {
                 self.device.destroy_framebuffer(framebuffer)
             }
-
-            self.command_pool.reset();
-
-            self.device
-                .destroy_render_pass(ManuallyDrop::into_inner(read(&mut self.render_pass)));
-
             for image_view in self.image_views.drain(..) {
                 self.device.destroy_image_view(image_view)
             }
+            if let Some(swapchain) = self.swapchain.take() {
+                self.device.destroy_swapchain(swapchain);
+            }
+        }
+    }
 
-            // TODO: DESTROY GRAPHICAL PIPELINES AND LAYOUTS HERE
+    fn destroy_pipelines(&mut self) {
+        for (_, graphics_pipeline) in self.pipelines.drain() {
+            unsafe { self.device.destroy_graphics_pipeline(graphics_pipeline) }
+        }
+    }
 
-            self.device
-                .destroy_swapchain(ManuallyDrop::into_inner(read(&mut self.swapchain)));
+    fn save_pipeline_cache(&self) {
+        let path = match &self.pipeline_cache_path {
+            Some(path) => path,
+            None => return,
+        };
+        let saved = unsafe { self.device.get_pipeline_cache_data(&self.pipeline_cache) }
+            .map_err(|e| format!("{:?}", e))
+            .and_then(|data| {
+                pipeline_cache::save(path, &self._adapter.info, &data).map_err(|e| e.to_string())
+            });
+        if let Err(e) = saved {
+            warn!("Couldn't save the pipeline cache to {}: {}", path.display(), e);
         }
     }
 }
+
+/// `size`, the physical size of the window, within what the surface allows. Some platforms
+/// leave the extent up to the swapchain, `extents.end` is then only the largest one allowed.
+/// That's also the one a window that's gone gets.
+fn swapchain_extent(size: Option<PhysicalSize>, extents: &Range<Extent2D>) -> Extent2D {
+    let physical = size.unwrap_or_else(|| {
+        PhysicalSize::new(f64::from(extents.end.width), f64::from(extents.end.height))
+    });
+    Extent2D {
+        width: (physical.width.round() as u32)
+            .max(extents.start.width)
+            .min(extents.end.width),
+        height: (physical.height.round() as u32)
+            .max(extents.start.height)
+            .min(extents.end.height),
+    }
+}
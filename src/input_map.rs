@@ -0,0 +1,332 @@
+//! Named actions and axes on top of `UserInput`, so the rest of the app asks for `quit` rather
+//! than for the Escape key. Bindings come from a TOML file, see `assets/input.toml`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use winit::MouseButton;
+use winit::VirtualKeyCode;
+
+use crate::user_input::UserInput;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// The bindings used without `--bindings`.
+pub const DEFAULT_BINDINGS: &str = include_str!("../assets/input.toml");
+
+/// How far one wheel line is for wheels that scroll by pixels.
+const PIXELS_PER_LINE: f64 = 20.0;
+
+/// Something an action or one side of an axis can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Button(MouseButton),
+    Wheel(WheelDirection),
+}
+
+impl Serialize for Binding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BindingTable::from(*self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let table = BindingTable::deserialize(deserializer)?;
+        match (table.key, table.button, table.wheel) {
+            (Some(key), None, None) => Ok(Binding::Key(key)),
+            (None, Some(button), None) => Ok(Binding::Button(button.into())),
+            (None, None, Some(direction)) => Ok(Binding::Wheel(direction)),
+            _ => Err(serde::de::Error::custom(
+                "a binding needs exactly one of `key`, `button` or `wheel`",
+            )),
+        }
+    }
+}
+
+/// A `Binding` as the bindings file has it, `{ key = "Space" }` and so on. Goes through a
+/// struct because toml 0.5 can't write enum variants with a value.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BindingTable {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<VirtualKeyCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    button: Option<ButtonName>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wheel: Option<WheelDirection>,
+}
+
+impl From<Binding> for BindingTable {
+    fn from(binding: Binding) -> Self {
+        match binding {
+            Binding::Key(key) => BindingTable {
+                key: Some(key),
+                ..BindingTable::default()
+            },
+            Binding::Button(button) => BindingTable {
+                button: Some(button.into()),
+                ..BindingTable::default()
+            },
+            Binding::Wheel(direction) => BindingTable {
+                wheel: Some(direction),
+                ..BindingTable::default()
+            },
+        }
+    }
+}
+
+/// `"Left"`, `"Right"` and `"Middle"`, other buttons by number. `{ Other = <n> }` is read too.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ButtonName {
+    Named(MouseButton),
+    Number(u8),
+}
+
+impl From<MouseButton> for ButtonName {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Other(number) => ButtonName::Number(number),
+            named => ButtonName::Named(named),
+        }
+    }
+}
+
+impl From<ButtonName> for MouseButton {
+    fn from(button: ButtonName) -> Self {
+        match button {
+            ButtonName::Named(button) => button,
+            ButtonName::Number(number) => MouseButton::Other(number),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WheelDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Axis {
+    #[serde(default)]
+    pub positive: Vec<Binding>,
+    #[serde(default)]
+    pub negative: Vec<Binding>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+    #[serde(default)]
+    actions: BTreeMap<String, Vec<Binding>>,
+    #[serde(default)]
+    axes: BTreeMap<String, Axis>,
+}
+
+impl InputMap {
+    /// Fails on bindings winit doesn't know and on inputs bound more than once, listing every
+    /// conflict.
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        let input_map: Self = toml::from_str(source).map_err(|e| e.to_string())?;
+        let conflicts = input_map.conflicts();
+        if conflicts.is_empty() {
+            Ok(input_map)
+        } else {
+            Err(conflicts.join(", "))
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        Self::from_toml(&source).map_err(|e| format!("Bad bindings in {}: {}", path.display(), e))
+    }
+
+    /// The bindings in the format `load` reads, for saving them after a rebind.
+    pub fn to_toml(&self) -> Result<String, String> {
+        // A `Value` puts plain values before tables when it's written, as TOML needs them
+        let value = toml::Value::try_from(self).map_err(|e| e.to_string())?;
+        toml::to_string(&value).map_err(|e| e.to_string())
+    }
+
+    /// Adds `binding` to `action`, unless something else already uses it. In that case the
+    /// error names the action or axis that has it.
+    pub fn bind(&mut self, action: &str, binding: Binding) -> Result<(), String> {
+        match self.owner(binding) {
+            Some(Owner::Action(owner)) if owner == action => Ok(()),
+            Some(owner) => Err(owner.to_string()),
+            None => {
+                self.actions
+                    .entry(action.to_owned())
+                    .or_insert_with(Vec::new)
+                    .push(binding);
+                Ok(())
+            }
+        }
+    }
+
+    /// Like `bind` for one side of an axis.
+    pub fn bind_axis(&mut self, axis: &str, positive: bool, binding: Binding) -> Result<(), String> {
+        match self.owner(binding) {
+            Some(Owner::Axis(owner, side)) if owner == axis && side == positive => Ok(()),
+            Some(owner) => Err(owner.to_string()),
+            None => {
+                let axis = self.axes.entry(axis.to_owned()).or_insert_with(Axis::default);
+                if positive {
+                    axis.positive.push(binding);
+                } else {
+                    axis.negative.push(binding);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Frees `binding` from whatever it was bound to, for rebinding it.
+    pub fn unbind(&mut self, binding: Binding) {
+        for bindings in self.actions.values_mut() {
+            bindings.retain(|&b| b != binding);
+        }
+        for axis in self.axes.values_mut() {
+            axis.positive.retain(|&b| b != binding);
+            axis.negative.retain(|&b| b != binding);
+        }
+    }
+
+    /// One message per input bound in more than one place.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut owners: Vec<(Binding, Vec<String>)> = vec![];
+        for (binding, owner) in self.bindings() {
+            let owner = owner.to_string();
+            match owners.iter_mut().find(|(b, _)| *b == binding) {
+                Some((_, binding_owners)) => binding_owners.push(owner),
+                None => owners.push((binding, vec![owner])),
+            }
+        }
+        owners
+            .into_iter()
+            .filter(|(_, binding_owners)| binding_owners.len() > 1)
+            .map(|(binding, binding_owners)| {
+                format!("{:?} is bound to {}", binding, binding_owners.join(" and "))
+            })
+            .collect()
+    }
+
+    /// True while any binding of `action` is held, or when a wheel it's bound to moved.
+    pub fn is_active(&self, input: &UserInput, action: &str) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|&binding| match binding {
+                Binding::Key(key) => input.is_down(key),
+                Binding::Button(button) => input.is_button_down(button),
+                Binding::Wheel(direction) => wheel(input, direction) > 0.0,
+            })
+    }
+
+    /// True on the frame `action` got triggered.
+    pub fn just_activated(&self, input: &UserInput, action: &str) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|&binding| match binding {
+                Binding::Key(key) => input.just_pressed(key),
+                Binding::Button(button) => input.button_just_pressed(button),
+                Binding::Wheel(direction) => wheel(input, direction) > 0.0,
+            })
+    }
+
+    pub fn just_deactivated(&self, input: &UserInput, action: &str) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|&binding| match binding {
+                Binding::Key(key) => input.just_released(key),
+                Binding::Button(button) => input.button_just_released(button),
+                Binding::Wheel(_) => false,
+            })
+    }
+
+    /// Held keys and buttons count 1, wheels as many lines as they moved. The result is
+    /// clamped to -1..1.
+    pub fn axis(&self, input: &UserInput, axis: &str) -> f32 {
+        let axis = match self.axes.get(axis) {
+            Some(axis) => axis,
+            None => return 0.0,
+        };
+        let amount = |bindings: &[Binding]| -> f32 {
+            bindings
+                .iter()
+                .map(|&binding| match binding {
+                    Binding::Key(key) => input.is_down(key) as u8 as f32,
+                    Binding::Button(button) => input.is_button_down(button) as u8 as f32,
+                    Binding::Wheel(direction) => wheel(input, direction),
+                })
+                .sum()
+        };
+        (amount(&axis.positive) - amount(&axis.negative)).max(-1.0).min(1.0)
+    }
+
+    fn action_bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[][..], Vec::as_slice)
+    }
+
+    /// Every binding along with what it's bound to.
+    fn bindings(&self) -> Vec<(Binding, Owner)> {
+        let mut bindings = vec![];
+        for (action, action_bindings) in &self.actions {
+            for &binding in action_bindings {
+                bindings.push((binding, Owner::Action(action)));
+            }
+        }
+        for (name, axis) in &self.axes {
+            for &binding in &axis.positive {
+                bindings.push((binding, Owner::Axis(name, true)));
+            }
+            for &binding in &axis.negative {
+                bindings.push((binding, Owner::Axis(name, false)));
+            }
+        }
+        bindings
+    }
+
+    fn owner(&self, binding: Binding) -> Option<Owner> {
+        self.bindings()
+            .into_iter()
+            .find(|&(b, _)| b == binding)
+            .map(|(_, owner)| owner)
+    }
+}
+
+/// What a binding is bound to, the bool of an axis being its positive side.
+enum Owner<'a> {
+    Action(&'a str),
+    Axis(&'a str, bool),
+}
+
+impl fmt::Display for Owner<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Owner::Action(action) => write!(f, "action `{}`", action),
+            Owner::Axis(axis, true) => write!(f, "axis `{}`+", axis),
+            Owner::Axis(axis, false) => write!(f, "axis `{}`-", axis),
+        }
+    }
+}
+
+/// How many lines the wheel moved in `direction` this frame, 0 if it went the other way.
+fn wheel(input: &UserInput, direction: WheelDirection) -> f32 {
+    let (lines_x, lines_y) = input.scroll_lines;
+    let (pixels_x, pixels_y) = input.scroll_pixels;
+    let x = lines_x + (pixels_x / PIXELS_PER_LINE) as f32;
+    let y = lines_y + (pixels_y / PIXELS_PER_LINE) as f32;
+    let amount = match direction {
+        WheelDirection::Up => y,
+        WheelDirection::Down => -y,
+        WheelDirection::Right => x,
+        WheelDirection::Left => -x,
+    };
+    amount.max(0.0)
+}
@@ -0,0 +1,111 @@
+//! Records the input of every frame to a file and plays it back, so a bug can be reproduced
+//! from the recording instead of by hand.
+//!
+//! The file is a header followed by one bincode `Frame` per frame, written as it goes so a
+//! recording of a crash is still readable.
+
+use serde::{Deserialize, Serialize};
+
+use crate::user_input::{InputEvent, TimedEvent, UserInput};
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Bumped whenever `Frame` or `InputEvent` change shape.
+const HEADER: &[u8; 8] = b"NGFXREC1";
+
+#[derive(Serialize, Deserialize)]
+struct Frame {
+    frame_time: Duration,
+    /// Times are since the recording started.
+    events: Vec<(Duration, InputEvent)>,
+}
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("Couldn't record to {}: {}", path.display(), e);
+        let mut writer = BufWriter::new(File::create(path).map_err(error)?);
+        writer.write_all(HEADER).map_err(error)?;
+        Ok(Self {
+            writer,
+            started: Instant::now(),
+        })
+    }
+
+    /// Appends the frame `input` was just polled for.
+    pub fn record(&mut self, input: &UserInput) -> Result<(), String> {
+        let started = self.started;
+        let frame = Frame {
+            frame_time: input.frame_time,
+            events: input
+                .events
+                .iter()
+                .map(|timed| (timed.time.duration_since(started), timed.event))
+                .collect(),
+        };
+        bincode::serialize_into(&mut self.writer, &frame)
+            .map_err(|e| format!("Couldn't record a frame: {}", e))?;
+        // Every frame is on disk before the next one runs, in case that one crashes
+        self.writer
+            .flush()
+            .map_err(|e| format!("Couldn't record a frame: {}", e))
+    }
+}
+
+pub struct Replay {
+    reader: BufReader<File>,
+    started: Instant,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("Couldn't replay {}: {}", path.display(), e);
+        let mut reader = BufReader::new(File::open(path).map_err(error)?);
+        let mut header = [0; 8];
+        reader.read_exact(&mut header).map_err(error)?;
+        if &header != HEADER {
+            return Err(format!(
+                "{} isn't a recording from this version",
+                path.display()
+            ));
+        }
+        Ok(Self {
+            reader,
+            started: Instant::now(),
+        })
+    }
+
+    /// Feeds the next recorded frame to `input` in place of a poll. False once the recording
+    /// is over.
+    pub fn next_frame(&mut self, input: &mut UserInput) -> Result<bool, String> {
+        let frame: Frame = match bincode::deserialize_from(&mut self.reader) {
+            Ok(frame) => frame,
+            Err(e) => {
+                return match *e {
+                    bincode::ErrorKind::Io(ref io) if io.kind() == ErrorKind::UnexpectedEof => {
+                        Ok(false)
+                    }
+                    _ => Err(format!("Couldn't replay a frame: {}", e)),
+                }
+            }
+        };
+        let started = self.started;
+        let events = frame
+            .events
+            .into_iter()
+            .map(|(time, event)| TimedEvent {
+                time: started + time,
+                event,
+            })
+            .collect();
+        input.replay(frame.frame_time, events);
+        Ok(true)
+    }
+}
@@ -0,0 +1,45 @@
+//! A small renderer on top of gfx-hal and winit.
+//!
+//! Most programs implement [`App`] and hand it to a [`Runner`], which opens the window, owns
+//! the [`HalState`] and runs the loop: input from [`UserInput`] and the [`InputMap`] bindings,
+//! updates on a fixed timestep and a render every frame. Apps split into a [`Simulation`] and
+//! a [`Renderer`] can have the rendering on a thread of its own instead. See the `examples`
+//! directory.
+//!
+//! Shaders live in `pre_assets/shaders` and get compiled and reflected by the build script,
+//! see the [`shaders`] module for what comes out of it.
+//! With the `hot-reload` feature, a `shader_reload::ShaderReloader` recompiles edits to them
+//! while the app runs, and `Shadertoy` draws Shadertoy-style effects.
+
+mod app;
+mod files;
+mod frame_limiter;
+mod hal_state;
+pub mod input_map;
+pub mod input_record;
+pub mod local_state;
+pub mod pipeline_cache;
+mod render_thread;
+#[cfg(feature = "hot-reload")]
+pub mod shader_reload;
+pub mod shaders;
+#[cfg(feature = "hot-reload")]
+mod shadertoy;
+mod texture;
+pub mod timestep;
+pub mod user_input;
+pub mod window_geometry;
+pub mod winit_state;
+
+pub use crate::app::{App, Frame, RedrawMode, RedrawRequester, Runner};
+pub use crate::frame_limiter::FrameStats;
+pub use crate::hal_state::HalState;
+pub use crate::input_map::InputMap;
+pub use crate::local_state::LocalState;
+pub use crate::render_thread::{Renderer, Simulation};
+pub use crate::shaders::ShaderPair;
+#[cfg(feature = "hot-reload")]
+pub use crate::shadertoy::Shadertoy;
+pub use crate::timestep::{RenderTime, UpdateTime};
+pub use crate::user_input::UserInput;
+pub use crate::winit_state::{MonitorInfo, WindowConfig, WindowMode, WinitState};
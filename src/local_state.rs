@@ -1,14 +1,21 @@
+use crate::input_map::InputMap;
 use crate::user_input::UserInput;
 
+/// Sizes and positions are logical, see `physical_frame_size` and `physical_mouse_position`
+/// for pixels.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct LocalState {
     pub frame_width: f64,
     pub frame_height: f64,
     pub mouse_x: f64,
     pub mouse_y: f64,
+    /// Physical pixels per logical one, zero before the first `update_from_input`.
+    pub hidpi_factor: f64,
+    /// While `select` is active, the left button by default.
+    pub mouse_down: bool,
 }
 impl LocalState {
-    pub fn update_from_input(&mut self, input: UserInput) {
+    pub fn update_from_input(&mut self, input: &UserInput, input_map: &InputMap) {
         if let Some(frame_size) = input.new_frame_size {
             self.frame_width = frame_size.0;
             self.frame_height = frame_size.1;
@@ -17,5 +24,22 @@ impl LocalState {
             self.mouse_x = position.0;
             self.mouse_y = position.1;
         }
+        self.hidpi_factor = input.hidpi_factor;
+        self.mouse_down = input_map.is_active(input, "select");
+    }
+
+    pub fn physical_frame_size(&self) -> (f64, f64) {
+        (
+            self.frame_width * self.hidpi_factor,
+            self.frame_height * self.hidpi_factor,
+        )
+    }
+
+    /// The pixel under the cursor, counted from the top left.
+    pub fn physical_mouse_position(&self) -> (f64, f64) {
+        (
+            self.mouse_x * self.hidpi_factor,
+            self.mouse_y * self.hidpi_factor,
+        )
     }
 }
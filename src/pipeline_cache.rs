@@ -0,0 +1,54 @@
+//! Keeps the driver's pipeline cache between runs, in `nicegfx/pipelines.bin` under the user
+//! cache directory.
+//!
+//! The file starts with a line naming the adapter and the build that wrote it. A cache written
+//! for another GPU or by another version is thrown away instead of being handed to the driver.
+
+use gfx_hal::adapter::AdapterInfo;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::files;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "pipelines.bin";
+
+/// Where the cache lives, `None` when there's no cache directory to put it in.
+pub fn path() -> Option<PathBuf> {
+    files::cache_dir().map(|dir| dir.join(FILE_NAME))
+}
+
+/// The cached data for `adapter`, `None` if there's no cache yet or it belongs to another
+/// adapter.
+pub fn load(path: &Path, adapter: &AdapterInfo) -> Option<Vec<u8>> {
+    let contents = std::fs::read(path).ok()?;
+    let header_end = contents.iter().position(|&byte| byte == b'\n')?;
+    if contents[..header_end] != *identity(adapter).as_bytes() {
+        info!("{} was written for another adapter, ignoring it", path.display());
+        return None;
+    }
+    Some(contents[header_end + 1..].to_vec())
+}
+
+pub fn save(path: &Path, adapter: &AdapterInfo, data: &[u8]) -> io::Result<()> {
+    let mut contents = identity(adapter).into_bytes();
+    contents.push(b'\n');
+    contents.extend_from_slice(data);
+    files::write_atomically(path, &contents)
+}
+
+/// gfx-hal doesn't tell the driver version, the driver checks that part against its own
+/// header when the data gets loaded.
+fn identity(adapter: &AdapterInfo) -> String {
+    format!(
+        "nicegfx {} {:04x}:{:04x} {:?} {}",
+        env!("CARGO_PKG_VERSION"),
+        adapter.vendor,
+        adapter.device,
+        adapter.device_type,
+        adapter.name.replace('\n', " ")
+    )
+}
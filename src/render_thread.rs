@@ -0,0 +1,224 @@
+//! Rendering on a thread of its own, so a slow frame doesn't hold up input and window events.
+//! The event thread runs a `Simulation` and sends snapshots of it to a `Renderer`, which owns
+//! the `HalState` and draws them. See `Runner::run_threaded`.
+//!
+//! Snapshots that arrive while the renderer is still busy with the last one are dropped, the
+//! next frame brings a newer one anyway. Resizes always get through, in order.
+
+use winit::Window;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::app::Frame;
+use crate::frame_limiter::{FrameLimiter, STATS_INTERVAL};
+use crate::hal_state::HalState;
+use crate::input_map::InputMap;
+use crate::shaders::ShaderPair;
+use crate::timestep::{RenderTime, UpdateTime};
+use crate::user_input::UserInput;
+use crate::winit_state;
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The half of an app that stays on the event thread.
+pub trait Simulation {
+    /// What the renderer needs to draw a frame, copied out of the simulation.
+    type Snapshot: Send + 'static;
+
+    /// Called once a frame with everything that happened since the last one.
+    fn update(&mut self, input: &UserInput, input_map: &InputMap);
+
+    /// Called zero or more times a frame, once per fixed step of the simulation.
+    fn fixed_update(&mut self, _time: &UpdateTime) {}
+
+    /// Called with the logical size of the window, once at the start and after each resize.
+    fn on_resize(&mut self, _width: f64, _height: f64) {}
+
+    /// Called for each frame that goes to the renderer.
+    fn snapshot(&mut self) -> Self::Snapshot;
+
+    /// As `App::needs_redraw`, asked after the updates of a frame and again after its
+    /// snapshot.
+    fn needs_redraw(&self) -> bool {
+        true
+    }
+}
+
+/// The half of an app that lives on the render thread.
+pub trait Renderer: Send + 'static {
+    type Snapshot: Send + 'static;
+
+    /// The shaders the `HalState` is created with.
+    fn shaders(&self) -> ShaderPair;
+
+    /// As `App::init`, called on the render thread with the `HalState`.
+    fn init(&mut self, _hal_state: &mut HalState) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn render(&mut self, snapshot: &Self::Snapshot, frame: &mut Frame) -> Result<(), &'static str>;
+}
+
+enum Message<S> {
+    Frame {
+        snapshot: S,
+        time: RenderTime,
+        focused: bool,
+    },
+    /// The window changed size, the swapchain gets recreated before the next frame.
+    Resize,
+}
+
+/// The event thread's end of the render thread.
+pub(crate) struct RenderThread<S> {
+    /// Room for one frame, the one the renderer picks up next.
+    sender: Option<SyncSender<Message<S>>>,
+    rendered: Receiver<()>,
+    handle: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl<S: Send + 'static> RenderThread<S> {
+    pub fn spawn<R>(window: Arc<Window>, renderer: R, frame_limiter: FrameLimiter) -> Self
+    where
+        R: Renderer<Snapshot = S>,
+    {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let (rendered_sender, rendered) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name("render".to_owned())
+            .spawn(move || render_loop(&window, renderer, frame_limiter, receiver, rendered_sender))
+            .expect("Couldn't spawn the render thread");
+        Self {
+            sender: Some(sender),
+            rendered,
+            handle: Some(handle),
+        }
+    }
+
+    /// Hands a frame to the renderer unless it's still busy. False if the frame was dropped.
+    pub fn send_frame(
+        &mut self,
+        snapshot: S,
+        time: RenderTime,
+        focused: bool,
+    ) -> Result<bool, String> {
+        let message = Message::Frame {
+            snapshot,
+            time,
+            focused,
+        };
+        match self.sender().try_send(message) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(_)) => Ok(false),
+            Err(TrySendError::Disconnected(_)) => Err(self.stopped()),
+        }
+    }
+
+    /// Has the renderer recreate its swapchain. Waits for room in the channel, so frames
+    /// sent before still get drawn at the old size first.
+    pub fn resize(&mut self) -> Result<(), String> {
+        match self.sender().send(Message::Resize) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.stopped()),
+        }
+    }
+
+    /// Waits up to `timeout` for the renderer to finish a frame. The event thread keeps up
+    /// with the renderer this way without a slow frame keeping it from the input for long.
+    pub fn wait_rendered(&mut self, timeout: Duration) -> Result<(), String> {
+        match self.rendered.recv_timeout(timeout) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => Ok(()),
+            Err(RecvTimeoutError::Disconnected) => Err(self.stopped()),
+        }
+    }
+
+    /// Lets the renderer finish its last frame and drop the `HalState`, then joins it.
+    pub fn finish(&mut self) -> Result<(), String> {
+        // The render loop ends once the channel is closed
+        self.sender = None;
+        match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .unwrap_or_else(|_| Err("The render thread panicked".to_owned())),
+            None => Ok(()),
+        }
+    }
+
+    fn sender(&self) -> &SyncSender<Message<S>> {
+        self.sender.as_ref().expect("The render thread is finished")
+    }
+
+    /// The error the render thread stopped with.
+    fn stopped(&mut self) -> String {
+        match self.finish() {
+            Err(e) => e,
+            Ok(()) => "The render thread stopped".to_owned(),
+        }
+    }
+}
+
+impl<S> Drop for RenderThread<S> {
+    /// The `HalState` has to go before the window does, so an event loop bailing out with an
+    /// error still waits for it.
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(handle) = self.handle.take() {
+            if let Ok(Err(e)) = handle.join() {
+                error!("{}", e);
+            }
+        }
+    }
+}
+
+fn render_loop<R: Renderer>(
+    window: &Window,
+    mut renderer: R,
+    mut frame_limiter: FrameLimiter,
+    receiver: Receiver<Message<R::Snapshot>>,
+    rendered: mpsc::Sender<()>,
+) -> Result<(), String> {
+    let mut hal_state = create_hal_state(window, &mut renderer)?;
+    let mut stats_logged = Instant::now();
+    for message in receiver {
+        match message {
+            Message::Frame {
+                snapshot,
+                time,
+                focused,
+            } => {
+                let mut frame = Frame {
+                    hal_state: &mut hal_state,
+                    time,
+                    stats: frame_limiter.stats(),
+                };
+                if let Err(e) = renderer.render(&snapshot, &mut frame) {
+                    error!("{:#?}", e);
+                }
+                frame_limiter.wait(focused);
+                if stats_logged.elapsed() >= STATS_INTERVAL {
+                    info!("{}", frame_limiter.stats());
+                    stats_logged = Instant::now();
+                }
+                // Nobody listening just means the event thread is on its way out
+                let _ = rendered.send(());
+            }
+            Message::Resize => {
+                // Frames get skipped until a later resize gets a swapchain made
+                if let Err(e) = hal_state.recreate_swapchain(winit_state::physical_size(window)) {
+                    warn!("Couldn't recreate the swapchain: {}", e);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn create_hal_state<R: Renderer>(window: &Window, renderer: &mut R) -> Result<HalState, String> {
+    let mut hal_state = HalState::new(window, renderer.shaders())?;
+    renderer.init(&mut hal_state)?;
+    Ok(hal_state)
+}
@@ -0,0 +1,316 @@
+//! Recompiles the shaders while the app runs, with the same preprocessor and compiler the build
+//! uses, so a shader tweak shows up without a restart. The sources are read from a directory
+//! laid out like `pre_assets/shaders`, wherever the app keeps it.
+//!
+//! Only the SPIR-V gets swapped. The pipeline layout still comes from the bindings generated at
+//! build time, so a shader whose interface changed is rejected until the next `cargo build`.
+//!
+//! The `Runner` leaves it to the app, which knows where its sources are and which shaders it
+//! draws with. An app keeps a `ShaderReloader`, calls `force` in `App::init`, and in
+//! `App::render` hands what `compile` makes of its shaders to `HalState::replace_shaders`
+//! whenever `changed` says so. `examples/triangle.rs` does just that, and `Shadertoy` watches
+//! its effect file the same way.
+
+#[allow(dead_code)]
+#[path = "../build/bindings.rs"]
+mod bindings;
+#[allow(dead_code)]
+#[path = "../build/preprocess.rs"]
+mod preprocess;
+#[allow(dead_code)]
+#[path = "../build/diagnostics.rs"]
+mod diagnostics;
+#[allow(dead_code)]
+#[path = "../build/reflect.rs"]
+mod reflect;
+
+use self::diagnostics::Diagnostic;
+use crate::shaders::{Attribute, DescriptorBinding, ShaderInterface};
+
+use gfx_hal::pso::DescriptorType;
+
+use glsl_to_spirv::ShaderType;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the watched sources get checked for changes.
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct ShaderReloader {
+    root: PathBuf,
+    /// Files, and directories whose files are all watched.
+    watched: Vec<PathBuf>,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_check: Instant,
+    forced: bool,
+}
+
+impl ShaderReloader {
+    /// Starts watching the shader sources under `root`, treating what's there now as already
+    /// loaded.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        let root = root.into();
+        if !root.is_dir() {
+            warn!("{} doesn't exist, shader hot reload is off", root.display());
+        }
+        Self::watching(root.clone(), vec![root])
+    }
+
+    /// Only watches `files`, for sources that aren't under `root`. `compile` still reads the
+    /// shaders from `root`.
+    pub fn watching<P: Into<PathBuf>>(root: P, files: Vec<PathBuf>) -> Self {
+        let modified = scan(&files);
+        Self {
+            root: root.into(),
+            watched: files,
+            modified,
+            last_check: Instant::now(),
+            forced: false,
+        }
+    }
+
+    /// True when a watched source changed since the last time this returned true.
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let modified = scan(&self.watched);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed || std::mem::replace(&mut self.forced, false)
+    }
+
+    /// Makes the next `changed` return true, for when the pipeline was rebuilt from the
+    /// shaders embedded at build time.
+    pub fn force(&mut self) {
+        self.forced = true;
+    }
+
+    /// Compiles the shader behind `interface` from its current source, logging what went wrong
+    /// if it doesn't compile or doesn't match the interface it was built with.
+    pub fn compile(&self, interface: &ShaderInterface) -> Option<Vec<u8>> {
+        logged(
+            self.compile_variant(interface.name)
+                .and_then(|spirv| checked(spirv, interface)),
+        )
+    }
+
+    /// `name` is a file name like `simple.frag` or a permutation like `simple.high.frag` or
+    /// `simple.high.fog.frag`.
+    fn compile_variant(&self, name: &str) -> Result<Vec<u8>, Vec<Diagnostic>> {
+        use std::io::Read;
+
+        let error = |message: String| vec![Diagnostic::error(message)];
+        let (stem, permutation, extension) = match (name.find('.'), name.rfind('.')) {
+            (Some(first), Some(last)) if first == last => (&name[..first], None, &name[last + 1..]),
+            (Some(first), Some(last)) => (
+                &name[..first],
+                Some(&name[first + 1..last]),
+                &name[last + 1..],
+            ),
+            _ => return Err(error(format!("`{}` isn't a shader name", name))),
+        };
+        let shader_type = match extension {
+            "vert" => ShaderType::Vertex,
+            "frag" => ShaderType::Fragment,
+            _ => return Err(error(format!("`{}` isn't a shader name", name))),
+        };
+
+        let path = self.root.join(format!("{}.{}", stem, extension));
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| error(format!("couldn't read {}: {}", path.display(), e)))?;
+        let defines = match permutation {
+            Some(permutation) => preprocess::permutations(&path, &source)
+                .map_err(|e| vec![e.into()])?
+                .into_iter()
+                .find(|p| p.name == permutation)
+                .ok_or_else(|| error(format!("{} has no `{}` permutation", stem, permutation)))?
+                .defines,
+            None => vec![],
+        };
+        let preprocessed =
+            preprocess::preprocess(&self.root, &path, &defines).map_err(|e| vec![e.into()])?;
+
+        let mut compiled_file = glsl_to_spirv::compile(&preprocessed.source, shader_type)
+            .map_err(|output| diagnostics::parse_glslang(&output, &preprocessed))?;
+        let mut compiled_bytes = Vec::new();
+        compiled_file
+            .read_to_end(&mut compiled_bytes)
+            .map_err(|e| error(format!("couldn't read the compiled {}: {}", name, e)))?;
+        Ok(compiled_bytes)
+    }
+}
+
+/// Compiles the fragment shader at `path`, which doesn't have to be under the shader root,
+/// with the source at `prelude` pasted in front of it. It has to match `interface` like any
+/// reloaded shader.
+pub fn compile_fragment(
+    path: &Path,
+    prelude: &Path,
+    interface: &ShaderInterface,
+) -> Option<Vec<u8>> {
+    use std::io::Read;
+
+    let error = |message: String| vec![Diagnostic::error(message)];
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .map_err(|e| error(format!("couldn't read {}: {}", path.display(), e)))
+    };
+    let compile = || -> Result<Vec<u8>, Vec<Diagnostic>> {
+        let prelude_source = read(prelude)?;
+        let source = read(path)?;
+        let preprocessed = preprocess::Preprocessed::concat(&[
+            (None, "#version 450"),
+            (Some(prelude), &prelude_source),
+            (Some(path), &source),
+        ]);
+        let mut compiled_file = glsl_to_spirv::compile(&preprocessed.source, ShaderType::Fragment)
+            .map_err(|output| diagnostics::parse_glslang(&output, &preprocessed))?;
+        let mut compiled_bytes = Vec::new();
+        compiled_file.read_to_end(&mut compiled_bytes).map_err(|e| {
+            error(format!("couldn't read the compiled {}: {}", path.display(), e))
+        })?;
+        Ok(compiled_bytes)
+    };
+    logged(compile().and_then(|spirv| checked(spirv, interface)))
+}
+
+/// Makes sure `spirv` still fits the pipeline layout generated for `interface`.
+fn checked(spirv: Vec<u8>, interface: &ShaderInterface) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    let module = reflect::reflect(&spirv).map_err(|e| {
+        let message = format!("couldn't reflect {}: {}", interface.name, e);
+        vec![Diagnostic::error(message)]
+    })?;
+    check_interface(&module, interface).map_err(|message| {
+        let message = format!(
+            "the interface of {} changed, rebuild to pick it up: {}",
+            interface.name, message
+        );
+        vec![Diagnostic::error(message)]
+    })?;
+    Ok(spirv)
+}
+
+fn logged(result: Result<Vec<u8>, Vec<Diagnostic>>) -> Option<Vec<u8>> {
+    match result {
+        Ok(spirv) => Some(spirv),
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                error!("{}", diagnostic);
+            }
+            None
+        }
+    }
+}
+
+/// Modification times of `paths` and of every file under the ones that are directories.
+fn scan(paths: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+    let mut modified = HashMap::new();
+    for path in paths {
+        scan_path(path, &mut modified);
+    }
+    modified
+}
+
+fn scan_path(path: &Path, modified: &mut HashMap<PathBuf, SystemTime>) {
+    match std::fs::metadata(path) {
+        Ok(ref metadata) if metadata.is_dir() => {
+            let entries = match std::fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(_) => return,
+            };
+            for entry in entries.filter_map(Result::ok) {
+                scan_path(&entry.path(), modified);
+            }
+        }
+        Ok(metadata) => {
+            if let Ok(time) = metadata.modified() {
+                modified.insert(path.to_owned(), time);
+            }
+        }
+        // Gone for now, it counts as changed once it's back
+        Err(_) => {}
+    }
+}
+
+/// The parts of the interface the pipeline layout and vertex input were built from.
+fn check_interface(module: &reflect::Module, interface: &ShaderInterface) -> Result<(), String> {
+    check_attributes("input", &module.inputs, interface.inputs)?;
+    check_attributes("output", &module.outputs, interface.outputs)?;
+
+    let mut descriptors: Vec<&reflect::Descriptor> = module.descriptors.iter().collect();
+    let mut expected_descriptors: Vec<&DescriptorBinding> =
+        interface.descriptor_bindings.iter().collect();
+    descriptors.sort_by_key(|d| (d.set, d.binding));
+    expected_descriptors.sort_by_key(|b| (b.set, b.binding));
+    let bindings = descriptors.iter().map(|d| (d.set, d.binding));
+    let expected_bindings = expected_descriptors.iter().map(|b| (b.set, b.binding));
+    if !bindings.eq(expected_bindings) {
+        return Err("the descriptor bindings differ".to_owned());
+    }
+    let types = descriptors.iter().map(|d| descriptor_type(d.kind));
+    let expected_types = expected_descriptors.iter().map(|b| b.ty);
+    if !types.eq(expected_types) {
+        return Err("the descriptor types differ".to_owned());
+    }
+    let counts = descriptors.iter().map(|d| d.count as usize);
+    let expected_counts = expected_descriptors.iter().map(|b| b.count);
+    if !counts.eq(expected_counts) {
+        return Err("the descriptor counts differ".to_owned());
+    }
+
+    let push_constants = module.push_constants.as_ref().map(reflect::Type::size);
+    let expected_push_constants = interface.push_constants.as_ref().map(|range| range.end);
+    if push_constants != expected_push_constants {
+        return Err("the push constant block size differs".to_owned());
+    }
+    Ok(())
+}
+
+/// The locations of `variables` and then their formats have to match `expected`.
+fn check_attributes(
+    kind: &str,
+    variables: &[reflect::Variable],
+    expected: &[Attribute],
+) -> Result<(), String> {
+    let mut variables: Vec<&reflect::Variable> = variables.iter().collect();
+    let mut expected: Vec<&Attribute> = expected.iter().collect();
+    variables.sort_by_key(|v| v.location);
+    expected.sort_by_key(|a| a.location);
+    let locations = variables.iter().map(|v| v.location);
+    let expected_locations = expected.iter().map(|a| a.location);
+    if !locations.eq(expected_locations) {
+        return Err(format!("the {} locations differ", kind));
+    }
+    // By the names the generated bindings use
+    let formats = variables.iter().map(|v| bindings::format(&v.ty));
+    let expected_formats = expected
+        .iter()
+        .map(|a| a.format.map(|format| format!("{:?}", format)));
+    if !formats.eq(expected_formats) {
+        return Err(format!("the {} formats differ", kind));
+    }
+    Ok(())
+}
+
+fn descriptor_type(kind: reflect::DescriptorKind) -> DescriptorType {
+    use self::reflect::DescriptorKind as Kind;
+
+    match kind {
+        Kind::Sampler => DescriptorType::Sampler,
+        Kind::CombinedImageSampler => DescriptorType::CombinedImageSampler,
+        Kind::SampledImage => DescriptorType::SampledImage,
+        Kind::StorageImage => DescriptorType::StorageImage,
+        Kind::UniformTexelBuffer => DescriptorType::UniformTexelBuffer,
+        Kind::StorageTexelBuffer => DescriptorType::StorageTexelBuffer,
+        Kind::UniformBuffer => DescriptorType::UniformBuffer,
+        Kind::StorageBuffer => DescriptorType::StorageBuffer,
+        Kind::InputAttachment => DescriptorType::InputAttachment,
+    }
+}
@@ -0,0 +1,300 @@
+//! Interfaces of the shaders in `pre_assets/shaders`, reflected from the compiled SPIR-V by
+//! `build/main.rs`. Every shader gets a module named after its file, so `simple.frag` becomes
+//! `shaders::simple_frag`, holding its `INTERFACE`, location and binding constants and a
+//! `#[repr(C)]` struct for each block.
+//!
+//! The compiled modules are embedded in the binary and looked up by name with [`spirv`]. With
+//! the `shaders-from-disk` feature they are read from the build output instead, or from the
+//! directory in `NICEGFX_SHADER_DIR`, so shaders can be swapped without rebuilding the binary.
+
+use gfx_hal::{format::Format,
+              pso::{DescriptorSetLayoutBinding, DescriptorType, ShaderStageFlags,
+                    SpecializationConstant}};
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// A stage input or output.
+#[derive(Debug, Clone, Copy)]
+pub struct Attribute {
+    pub name: &'static str,
+    pub location: u32,
+    /// `None` for types that don't fit in a single vertex attribute, like matrices.
+    pub format: Option<Format>,
+}
+
+/// A resource the shader reads through a descriptor set.
+#[derive(Debug, Clone, Copy)]
+pub struct DescriptorBinding {
+    pub name: &'static str,
+    pub set: u32,
+    pub binding: u32,
+    pub ty: DescriptorType,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShaderInterface {
+    pub name: &'static str,
+    pub stage: ShaderStageFlags,
+    pub inputs: &'static [Attribute],
+    pub outputs: &'static [Attribute],
+    pub descriptor_bindings: &'static [DescriptorBinding],
+    /// Byte range of the push constant block.
+    pub push_constants: Option<Range<u32>>,
+}
+
+/// The vertex and fragment shader of a graphics pipeline.
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderPair {
+    pub vertex: &'static ShaderInterface,
+    pub fragment: &'static ShaderInterface,
+}
+
+impl ShaderPair {
+    pub fn stages(&self) -> [&'static ShaderInterface; 2] {
+        [self.vertex, self.fragment]
+    }
+}
+
+/// A value a specialization constant can take, stored as the 32 bits the shader reads.
+pub trait SpecConstantValue: Copy {
+    fn to_bits(self) -> u32;
+}
+
+impl SpecConstantValue for bool {
+    fn to_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+impl SpecConstantValue for u32 {
+    fn to_bits(self) -> u32 {
+        self
+    }
+}
+
+impl SpecConstantValue for i32 {
+    fn to_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+impl SpecConstantValue for f32 {
+    fn to_bits(self) -> u32 {
+        f32::to_bits(self)
+    }
+}
+
+/// Values for the specialization constants of one stage, keyed by `constant_id` like the ids
+/// in the `spec_constants` module of each shader. Constants left out keep their defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SpecConstants {
+    /// `(constant_id, bits)`, sorted by id so equal sets of values compare equal.
+    values: Vec<(u32, u32)>,
+}
+
+impl SpecConstants {
+    pub fn with<T: SpecConstantValue>(mut self, id: u32, value: T) -> Self {
+        self.set(id, value);
+        self
+    }
+
+    pub fn set<T: SpecConstantValue>(&mut self, id: u32, value: T) {
+        match self.values.binary_search_by_key(&id, |&(id, _)| id) {
+            Ok(index) => self.values[index].1 = value.to_bits(),
+            Err(index) => self.values.insert(index, (id, value.to_bits())),
+        }
+    }
+
+    /// The constant table and the data it points into, what `Specialization` borrows.
+    pub fn specialization(&self) -> (Vec<SpecializationConstant>, Vec<u8>) {
+        let mut constants = Vec::with_capacity(self.values.len());
+        let mut data = Vec::with_capacity(self.values.len() * 4);
+        for &(id, bits) in &self.values {
+            let start = data.len() as u16;
+            data.extend_from_slice(&bits.to_ne_bytes());
+            constants.push(SpecializationConstant {
+                id,
+                range: start..data.len() as u16,
+            });
+        }
+        (constants, data)
+    }
+}
+
+/// Picks one specialization of a pipeline, pipelines are built and cached per key.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SpecializationKey {
+    pub vertex: SpecConstants,
+    pub fragment: SpecConstants,
+}
+
+/// The `simple` triangle.
+pub const TRIANGLE: ShaderPair = ShaderPair {
+    vertex: &simple_vert::INTERFACE,
+    fragment: &simple_frag::INTERFACE,
+};
+
+/// A textured quad, drawn as 6 vertices.
+pub const QUAD: ShaderPair = ShaderPair {
+    vertex: &quad_vert::INTERFACE,
+    fragment: &quad_frag::INTERFACE,
+};
+
+include!(concat!(env!("OUT_DIR"), "/shader_bindings.rs"));
+include!(concat!(env!("OUT_DIR"), "/shader_table.rs"));
+
+/// The compiled SPIR-V of a shader, `name` being its file name like `simple.vert`.
+#[cfg(not(feature = "shaders-from-disk"))]
+pub fn spirv(name: &str) -> Option<Cow<'static, [u8]>> {
+    EMBEDDED_SPIRV
+        .iter()
+        .find(|(embedded, _)| *embedded == name)
+        .map(|(_, bytes)| Cow::Borrowed(*bytes))
+}
+
+/// The compiled SPIR-V of a shader, `name` being its file name like `simple.vert`.
+#[cfg(feature = "shaders-from-disk")]
+pub fn spirv(name: &str) -> Option<Cow<'static, [u8]>> {
+    let dir = std::env::var_os("NICEGFX_SHADER_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| concat!(env!("OUT_DIR"), "/shaders").into());
+    std::fs::read(dir.join(format!("{}.spv", name)))
+        .map(Cow::Owned)
+        .map_err(|e| log::error!("Couldn't load the {} shader: {}", name, e))
+        .ok()
+}
+
+/// The descriptor set layouts used by all of `stages`, indexed by set number. Stages sharing a
+/// binding have to agree on what it is.
+pub fn set_layout_bindings(
+    stages: &[&ShaderInterface],
+) -> Result<Vec<Vec<DescriptorSetLayoutBinding>>, String> {
+    let set_count = stages
+        .iter()
+        .flat_map(|stage| stage.descriptor_bindings)
+        .map(|binding| binding.set as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let mut sets: Vec<Vec<DescriptorSetLayoutBinding>> = vec![vec![]; set_count];
+    // The stage each binding was first seen in, to name it when another one disagrees
+    let mut first_stages: Vec<(&ShaderInterface, &DescriptorBinding)> = vec![];
+    for stage in stages {
+        for binding in stage.descriptor_bindings {
+            let set = &mut sets[binding.set as usize];
+            // Resources shared between stages only get one binding
+            match set.iter_mut().find(|b| b.binding == binding.binding) {
+                Some(existing) => {
+                    if existing.ty != binding.ty || existing.count != binding.count {
+                        let (first_stage, first) = first_stages
+                            .iter()
+                            .find(|(_, b)| (b.set, b.binding) == (binding.set, binding.binding))
+                            .expect("A binding without the stage it came from");
+                        return Err(format!(
+                            "`{}` and `{}` disagree on set {} binding {}: {} {:?} and {} {:?}",
+                            first_stage.name,
+                            stage.name,
+                            binding.set,
+                            binding.binding,
+                            first.count,
+                            first.ty,
+                            binding.count,
+                            binding.ty
+                        ));
+                    }
+                    existing.stage_flags |= stage.stage;
+                }
+                None => {
+                    set.push(DescriptorSetLayoutBinding {
+                        binding: binding.binding,
+                        ty: binding.ty,
+                        count: binding.count,
+                        stage_flags: stage.stage,
+                        immutable_samplers: false,
+                    });
+                    first_stages.push((*stage, binding));
+                }
+            }
+        }
+    }
+    Ok(sets)
+}
+
+/// The combined image sampler at `(set, binding)` in `stages`, what `HalState::set_texture`
+/// can point at a texture.
+pub fn sampler_binding(
+    stages: &[&ShaderInterface],
+    (set, binding): (u32, u32),
+) -> Result<&'static DescriptorBinding, &'static str> {
+    let reflected = stages
+        .iter()
+        .flat_map(|stage| stage.descriptor_bindings)
+        .find(|reflected| (reflected.set, reflected.binding) == (set, binding))
+        .ok_or("The shaders don't have that binding")?;
+    if reflected.ty == DescriptorType::CombinedImageSampler {
+        Ok(reflected)
+    } else {
+        Err("That binding isn't a combined image sampler")
+    }
+}
+
+/// The push constant ranges of `stages`, ready for `create_pipeline_layout`.
+pub fn push_constant_ranges(stages: &[&ShaderInterface]) -> Vec<(ShaderStageFlags, Range<u32>)> {
+    stages
+        .iter()
+        .filter_map(|stage| {
+            stage
+                .push_constants
+                .clone()
+                .map(|range| (stage.stage, range))
+        })
+        .collect()
+}
+
+/// The pieces `HalState` pushes a frame's push constants in: the byte ranges of
+/// `push_constant_ranges`, cut where any of them starts or ends, each with every stage whose
+/// block covers it. Vulkan wants all the stages of the blocks overlapping what gets pushed.
+pub fn push_constant_updates(stages: &[&ShaderInterface]) -> Vec<(ShaderStageFlags, Range<u32>)> {
+    let ranges = push_constant_ranges(stages);
+    let mut bounds: Vec<u32> = ranges
+        .iter()
+        .flat_map(|(_, range)| vec![range.start, range.end])
+        .collect();
+    bounds.sort();
+    bounds.dedup();
+    bounds
+        .windows(2)
+        .filter_map(|piece| {
+            let stages = ranges
+                .iter()
+                .filter(|(_, range)| range.start <= piece[0] && piece[1] <= range.end)
+                .fold(ShaderStageFlags::empty(), |flags, (stage, _)| flags | *stage);
+            if stages.is_empty() {
+                None
+            } else {
+                Some((stages, piece[0]..piece[1]))
+            }
+        })
+        .collect()
+}
+
+/// A push constant block that can be viewed as `u32` words. The generated `PushConstants`
+/// structs implement it.
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]`, aligned to at least 4 bytes, a multiple of 4 bytes long
+/// and without padding the compiler inserts, which could be uninitialized.
+pub unsafe trait PushBlock: Copy {}
+
+/// Views a push constant block as the `u32` words `push_graphics_constants` wants.
+pub fn as_words<T: PushBlock>(block: &T) -> &[u32] {
+    // Sound by the contract of `PushBlock`
+    unsafe {
+        std::slice::from_raw_parts(
+            block as *const T as *const u32,
+            std::mem::size_of::<T>() / 4,
+        )
+    }
+}
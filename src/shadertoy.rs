@@ -0,0 +1,200 @@
+//! Shadertoy-style effects: a fragment shader that only defines `mainImage`, drawn over the
+//! whole window with the inputs declared in `pre_assets/shaders/shadertoy.glsl`. Without an
+//! effect file the built-in `shadertoy.frag` runs. Either way, edits show up while it runs.
+
+use gfx_hal::pso::Rect;
+
+use image::RgbaImage;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::app::{App, Frame};
+use crate::hal_state::HalState;
+use crate::input_map::InputMap;
+use crate::local_state::LocalState;
+use crate::shader_reload::{self, ShaderReloader};
+use crate::shaders::{self, shadertoy_frag, shadertoy_vert, ShaderPair};
+use crate::timestep::{seconds, RenderTime};
+use crate::user_input::UserInput;
+
+use std::path::PathBuf;
+
+pub const SHADERS: ShaderPair = ShaderPair {
+    vertex: &shadertoy_vert::INTERFACE,
+    fragment: &shadertoy_frag::INTERFACE,
+};
+
+/// `(set, binding)` of `iChannel0` to `iChannel3`.
+pub const CHANNELS: [(u32, u32); 4] = [
+    shadertoy_frag::bindings::I_CHANNEL0,
+    shadertoy_frag::bindings::I_CHANNEL1,
+    shadertoy_frag::bindings::I_CHANNEL2,
+    shadertoy_frag::bindings::I_CHANNEL3,
+];
+
+/// Goes in front of effect files, which don't declare any of the inputs themselves. Relative
+/// to the shader root.
+const PRELUDE: &str = "shadertoy.glsl";
+
+pub struct Shadertoy {
+    effect: Option<PathBuf>,
+    /// The last version of the effect that compiled.
+    spirv: Option<Vec<u8>>,
+    channels: Vec<((u32, u32), RgbaImage)>,
+    shader_root: PathBuf,
+    shader_reloader: ShaderReloader,
+    local_state: LocalState,
+    frame: i32,
+    mouse: [f32; 4],
+    mouse_was_down: bool,
+}
+
+impl Shadertoy {
+    /// Compiles `effect`, or picks the built-in one when it's `None`, and loads the images of
+    /// the channels, `channels[0]` going to `iChannel0` and so on. `shader_root` is where the
+    /// sources in `pre_assets/shaders` are, the built-in effect and the prelude come from there.
+    pub fn new<P: Into<PathBuf>>(
+        shader_root: P,
+        effect: Option<PathBuf>,
+        channels: &[Option<PathBuf>],
+    ) -> Result<Self, String> {
+        let shader_root = shader_root.into();
+        let mut loaded = vec![];
+        for (&binding, path) in CHANNELS.iter().zip(channels) {
+            if let Some(path) = path {
+                let image = image::open(path)
+                    .map_err(|e| format!("Couldn't load {}: {}", path.display(), e))?;
+                // Texture coordinates start at the bottom left in Shadertoy too
+                loaded.push((binding, image::imageops::flip_vertical(&image.to_rgba())));
+            }
+        }
+
+        // Effects can be anywhere, so only they and the prelude get watched
+        let shader_reloader = match &effect {
+            Some(effect) => {
+                let prelude = shader_root.join(PRELUDE);
+                ShaderReloader::watching(&shader_root, vec![effect.clone(), prelude])
+            }
+            None => ShaderReloader::new(&shader_root),
+        };
+        let mut shadertoy = Self {
+            effect,
+            spirv: None,
+            channels: loaded,
+            shader_root,
+            shader_reloader,
+            local_state: LocalState::default(),
+            frame: 0,
+            mouse: [0.0; 4],
+            mouse_was_down: false,
+        };
+        if let Some(effect) = &shadertoy.effect {
+            shadertoy.spirv = Some(
+                shadertoy
+                    .compile()
+                    .ok_or_else(|| format!("Couldn't compile {}", effect.display()))?,
+            );
+        }
+        Ok(shadertoy)
+    }
+
+    /// Picks up edits to the effect, keeping the running version if the new one is broken.
+    fn reload_if_changed(&mut self, hal_state: &mut HalState) {
+        if !self.shader_reloader.changed() {
+            return;
+        }
+        if let Some(spirv) = self.compile() {
+            match hal_state.replace_shaders(None, Some(spirv.clone())) {
+                Ok(()) => {
+                    info!("Reloaded the effect");
+                    self.spirv = Some(spirv);
+                }
+                Err(e) => error!("Couldn't reload the effect: {}", e),
+            }
+        }
+    }
+
+    /// The inputs of the next frame. The mouse and the resolution are in pixels of the frame,
+    /// with the origin at the bottom left.
+    fn next_inputs(
+        &mut self,
+        render_area: Rect,
+        render_time: &RenderTime,
+    ) -> shadertoy_frag::PushConstants {
+        let local_state = &self.local_state;
+        let width = f32::from(render_area.w);
+        let height = f32::from(render_area.h);
+        // Shadertoy counts pixels from the bottom left
+        let (x, y) = local_state.physical_mouse_position();
+        let (x, y) = (x as f32, height - y as f32);
+        match (local_state.mouse_down, self.mouse_was_down) {
+            (true, false) => self.mouse = [x, y, x, y],
+            (true, true) => {
+                self.mouse[0] = x;
+                self.mouse[1] = y;
+                self.mouse[3] = -self.mouse[3].abs();
+            }
+            (false, _) => {
+                self.mouse[2] = -self.mouse[2].abs();
+                self.mouse[3] = -self.mouse[3].abs();
+            }
+        }
+        self.mouse_was_down = local_state.mouse_down;
+
+        let inputs = shadertoy_frag::PushConstants {
+            i_mouse: self.mouse,
+            i_resolution: [width, height, 1.0],
+            i_time: seconds(render_time.total),
+            i_time_delta: seconds(render_time.frame_time),
+            i_frame: self.frame,
+        };
+        self.frame += 1;
+        inputs
+    }
+
+    fn compile(&self) -> Option<Vec<u8>> {
+        match &self.effect {
+            Some(effect) => {
+                let prelude = self.shader_root.join(PRELUDE);
+                shader_reload::compile_fragment(effect, &prelude, SHADERS.fragment)
+            }
+            None => self.shader_reloader.compile(SHADERS.fragment),
+        }
+    }
+}
+
+impl App for Shadertoy {
+    fn shaders(&self) -> ShaderPair {
+        SHADERS
+    }
+
+    /// Points the `HalState` at the effect and the channel images.
+    fn init(&mut self, hal_state: &mut HalState) -> Result<(), &'static str> {
+        for (binding, image) in &self.channels {
+            hal_state.set_texture(*binding, image)?;
+        }
+        if let Some(spirv) = &self.spirv {
+            hal_state.replace_shaders(None, Some(spirv.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, input: &UserInput, input_map: &InputMap) {
+        self.local_state.update_from_input(input, input_map);
+    }
+
+    fn render(&mut self, frame: &mut Frame) -> Result<(), &'static str> {
+        self.reload_if_changed(frame.hal_state);
+        let inputs = self.next_inputs(frame.hal_state.render_area, &frame.time);
+        // One triangle covering the frame, made up by the vertex shader
+        frame
+            .hal_state
+            .draw_vertices_frame([0.0, 0.0, 0.0, 1.0], 3, shaders::as_words(&inputs))
+    }
+
+    fn on_resize(&mut self, width: f64, height: f64) {
+        self.local_state.frame_width = width;
+        self.local_state.frame_height = height;
+    }
+}
@@ -0,0 +1,240 @@
+use gfx_hal::{adapter::{MemoryTypeId, PhysicalDevice},
+              buffer::Usage as BufferUsage,
+              command::{BufferImageCopy, OneShot},
+              device::Device,
+              format::{Aspects, Format, Swizzle},
+              image::{Access, Extent, Filter, Kind, Layout, Offset, SamplerInfo, SubresourceLayers,
+                      SubresourceRange, Tiling, Usage, ViewCapabilities, ViewKind, WrapMode},
+              memory::{Barrier, Dependencies, Properties, Requirements},
+              pso::PipelineStage,
+              Adapter,
+              Backend,
+              CommandPool,
+              CommandQueue,
+              Graphics};
+
+use image::RgbaImage;
+
+use std::mem::ManuallyDrop;
+use std::ptr::read;
+
+/// A buffer along with the memory backing it.
+pub struct BufferBundle<B: Backend> {
+    pub buffer: ManuallyDrop<B::Buffer>,
+    pub requirements: Requirements,
+    pub memory: ManuallyDrop<B::Memory>,
+}
+
+impl<B: Backend> BufferBundle<B> {
+    /// A buffer of at least `size` bytes in memory the CPU can write to.
+    pub fn new(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        size: usize,
+        usage: BufferUsage,
+    ) -> Result<Self, &'static str> {
+        unsafe {
+            let mut buffer = device
+                .create_buffer(size as u64, usage)
+                .map_err(|_| "Couldn't create a buffer")?;
+            let requirements = device.get_buffer_requirements(&buffer);
+            let memory_type_id = find_memory_type(adapter, &requirements, Properties::CPU_VISIBLE)
+                .ok_or("Couldn't find a memory type for the buffer")?;
+            let memory = device
+                .allocate_memory(memory_type_id, requirements.size)
+                .map_err(|_| "Couldn't allocate buffer memory")?;
+            device
+                .bind_buffer_memory(&memory, 0, &mut buffer)
+                .map_err(|_| "Couldn't bind the buffer memory")?;
+            Ok(Self {
+                buffer: ManuallyDrop::new(buffer),
+                requirements,
+                memory: ManuallyDrop::new(memory),
+            })
+        }
+    }
+
+    pub unsafe fn manually_drop(&self, device: &B::Device) {
+        device.destroy_buffer(ManuallyDrop::into_inner(read(&self.buffer)));
+        device.free_memory(ManuallyDrop::into_inner(read(&self.memory)));
+    }
+}
+
+/// An RGBA image uploaded to the GPU, ready to be sampled in a shader.
+pub struct LoadedImage<B: Backend> {
+    pub image: ManuallyDrop<B::Image>,
+    pub requirements: Requirements,
+    pub memory: ManuallyDrop<B::Memory>,
+    pub image_view: ManuallyDrop<B::ImageView>,
+    pub sampler: ManuallyDrop<B::Sampler>,
+}
+
+impl<B: Backend> LoadedImage<B> {
+    /// Uploads `img` through a staging buffer, blocking until the copy is done.
+    pub fn new(
+        adapter: &Adapter<B>,
+        device: &B::Device,
+        command_pool: &mut CommandPool<B, Graphics>,
+        command_queue: &mut CommandQueue<B, Graphics>,
+        img: &RgbaImage,
+    ) -> Result<Self, &'static str> {
+        unsafe {
+            // Rows of the staging buffer have to be aligned for the copy
+            let pixel_size = std::mem::size_of::<image::Rgba<u8>>();
+            let row_size = pixel_size * img.width() as usize;
+            let limits = adapter.physical_device.limits();
+            let row_alignment_mask = limits.optimal_buffer_copy_pitch_alignment as usize - 1;
+            let row_pitch = (row_size + row_alignment_mask) & !row_alignment_mask;
+            debug_assert!(row_pitch >= row_size);
+
+            let staging_bundle = BufferBundle::new(
+                adapter,
+                device,
+                row_pitch * img.height() as usize,
+                BufferUsage::TRANSFER_SRC,
+            )?;
+            let staging_range = 0..staging_bundle.requirements.size;
+            let mut writer = device
+                .acquire_mapping_writer::<u8>(&staging_bundle.memory, staging_range)
+                .map_err(|_| "Couldn't map the staging buffer")?;
+            for y in 0..img.height() as usize {
+                let row = &(**img)[y * row_size..(y + 1) * row_size];
+                let dest_base = y * row_pitch;
+                writer[dest_base..dest_base + row.len()].copy_from_slice(row);
+            }
+            device
+                .release_mapping_writer(writer)
+                .map_err(|_| "Couldn't unmap the staging buffer")?;
+
+            let mut the_image = device
+                .create_image(
+                    Kind::D2(img.width(), img.height(), 1, 1),
+                    1,
+                    Format::Rgba8Srgb,
+                    Tiling::Optimal,
+                    Usage::TRANSFER_DST | Usage::SAMPLED,
+                    ViewCapabilities::empty(),
+                )
+                .map_err(|_| "Couldn't create the image")?;
+            let requirements = device.get_image_requirements(&the_image);
+            let memory_type_id = find_memory_type(adapter, &requirements, Properties::DEVICE_LOCAL)
+                .ok_or("Couldn't find a memory type for the image")?;
+            let memory = device
+                .allocate_memory(memory_type_id, requirements.size)
+                .map_err(|_| "Couldn't allocate image memory")?;
+            device
+                .bind_image_memory(&memory, 0, &mut the_image)
+                .map_err(|_| "Couldn't bind the image memory")?;
+            let color_range = SubresourceRange {
+                aspects: Aspects::COLOR,
+                levels: 0..1,
+                layers: 0..1,
+            };
+            let image_view = device
+                .create_image_view(
+                    &the_image,
+                    ViewKind::D2,
+                    Format::Rgba8Srgb,
+                    Swizzle::NO,
+                    color_range.clone(),
+                )
+                .map_err(|_| "Couldn't create the image view")?;
+            let sampler = device
+                .create_sampler(SamplerInfo::new(Filter::Linear, WrapMode::Tile))
+                .map_err(|_| "Couldn't create the sampler")?;
+
+            // Undefined -> transfer destination -> copy -> shader readable
+            let mut cmd_buffer = command_pool.acquire_command_buffer::<OneShot>();
+            cmd_buffer.begin();
+            let to_transfer = Barrier::Image {
+                states: (Access::empty(), Layout::Undefined)
+                    ..(Access::TRANSFER_WRITE, Layout::TransferDstOptimal),
+                target: &the_image,
+                families: None,
+                range: color_range.clone(),
+            };
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TOP_OF_PIPE..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                &[to_transfer],
+            );
+            cmd_buffer.copy_buffer_to_image(
+                &staging_bundle.buffer,
+                &the_image,
+                Layout::TransferDstOptimal,
+                &[BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_width: (row_pitch / pixel_size) as u32,
+                    buffer_height: img.height(),
+                    image_layers: SubresourceLayers {
+                        aspects: Aspects::COLOR,
+                        level: 0,
+                        layers: 0..1,
+                    },
+                    image_offset: Offset { x: 0, y: 0, z: 0 },
+                    image_extent: Extent {
+                        width: img.width(),
+                        height: img.height(),
+                        depth: 1,
+                    },
+                }],
+            );
+            let to_shader_read = Barrier::Image {
+                states: (Access::TRANSFER_WRITE, Layout::TransferDstOptimal)
+                    ..(Access::SHADER_READ, Layout::ShaderReadOnlyOptimal),
+                target: &the_image,
+                families: None,
+                range: color_range,
+            };
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
+                Dependencies::empty(),
+                &[to_shader_read],
+            );
+            cmd_buffer.finish();
+
+            let upload_fence = device
+                .create_fence(false)
+                .map_err(|_| "Couldn't create an upload fence")?;
+            command_queue.submit_nosemaphores(Some(&cmd_buffer), Some(&upload_fence));
+            device
+                .wait_for_fence(&upload_fence, core::u64::MAX)
+                .map_err(|_| "Couldn't wait for the upload fence")?;
+            device.destroy_fence(upload_fence);
+            staging_bundle.manually_drop(device);
+            command_pool.free(Some(cmd_buffer));
+
+            Ok(Self {
+                image: ManuallyDrop::new(the_image),
+                requirements,
+                memory: ManuallyDrop::new(memory),
+                image_view: ManuallyDrop::new(image_view),
+                sampler: ManuallyDrop::new(sampler),
+            })
+        }
+    }
+
+    pub unsafe fn manually_drop(&self, device: &B::Device) {
+        device.destroy_sampler(ManuallyDrop::into_inner(read(&self.sampler)));
+        device.destroy_image_view(ManuallyDrop::into_inner(read(&self.image_view)));
+        device.destroy_image(ManuallyDrop::into_inner(read(&self.image)));
+        device.free_memory(ManuallyDrop::into_inner(read(&self.memory)));
+    }
+}
+
+fn find_memory_type<B: Backend>(
+    adapter: &Adapter<B>,
+    requirements: &Requirements,
+    properties: Properties,
+) -> Option<MemoryTypeId> {
+    adapter
+        .physical_device
+        .memory_properties()
+        .memory_types
+        .iter()
+        .enumerate()
+        .find(|&(id, memory_type)| {
+            requirements.type_mask & (1 << id) != 0 && memory_type.properties.contains(properties)
+        })
+        .map(|(id, _)| MemoryTypeId(id))
+}
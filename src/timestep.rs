@@ -0,0 +1,93 @@
+//! A fixed simulation step decoupled from the frame rate. Each frame's time goes into an
+//! accumulator, whole steps come out of it for the update and the remainder becomes the
+//! interpolation alpha of the render.
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use std::time::Duration;
+
+/// What an update step gets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpdateTime {
+    /// The fixed step, every update advances the simulation by exactly this much.
+    pub step: Duration,
+    /// Simulation time at the end of this step.
+    pub total: Duration,
+    /// The frame this step runs in took this long.
+    pub frame_time: Duration,
+}
+
+/// What the render gets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderTime {
+    pub frame_time: Duration,
+    /// Simulation time plus the part of a step that's left over, so it moves smoothly.
+    pub total: Duration,
+    /// How far along the next step the frame is, from 0 to 1. State should be drawn this far
+    /// between the previous step and the last one.
+    pub alpha: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    total: Duration,
+    frame_time: Duration,
+}
+
+impl FixedTimestep {
+    /// `max_steps` caps the updates of a single frame. When frames get slower than that can
+    /// catch up with, the simulation slows down instead of spiralling.
+    pub fn new(step: Duration, max_steps: u32) -> Self {
+        assert!(step > Duration::from_secs(0), "The step can't be zero");
+        Self {
+            step,
+            max_steps,
+            accumulator: Duration::from_secs(0),
+            total: Duration::from_secs(0),
+            frame_time: Duration::from_secs(0),
+        }
+    }
+
+    /// Adds a frame of `frame_time` and yields the update steps to run for it.
+    pub fn advance(&mut self, frame_time: Duration) -> impl Iterator<Item = UpdateTime> {
+        self.frame_time = frame_time;
+        self.accumulator += frame_time;
+        // Every whole step leaves the accumulator, even the ones dropped below
+        let step_nanos = self.step.as_nanos();
+        let accumulated = self.accumulator.as_nanos();
+        self.accumulator = Duration::from_nanos((accumulated % step_nanos) as u64);
+        let whole_steps = accumulated / step_nanos;
+        let steps = if whole_steps > u128::from(self.max_steps) {
+            debug!("Dropped {} update steps", whole_steps - u128::from(self.max_steps));
+            self.max_steps
+        } else {
+            whole_steps as u32
+        };
+
+        let start = self.total;
+        let step = self.step;
+        self.total += step * steps;
+        (1..=steps).map(move |n| UpdateTime {
+            step,
+            total: start + step * n,
+            frame_time,
+        })
+    }
+
+    /// Times for rendering the frame last passed to `advance`.
+    pub fn render_time(&self) -> RenderTime {
+        RenderTime {
+            frame_time: self.frame_time,
+            total: self.total + self.accumulator,
+            alpha: seconds(self.accumulator) / seconds(self.step),
+        }
+    }
+}
+
+pub fn seconds(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 * 1e-9
+}
@@ -1,33 +1,381 @@
+use serde::{Deserialize, Serialize};
+
+use winit::ControlFlow;
+use winit::DeviceEvent;
+use winit::ElementState;
 use winit::Event;
 use winit::EventsLoop;
+use winit::KeyboardInput;
+use winit::ModifiersState;
+use winit::MouseButton;
+use winit::MouseScrollDelta;
+use winit::VirtualKeyCode;
 use winit::WindowEvent;
 
-#[derive(Debug, Clone, Default)]
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// The input events we care about, translated from winit. Positions and sizes are in logical
+/// pixels like winit's, multiply by the hidpi factor for physical ones.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    CloseRequested,
+    Resized {
+        width: f64,
+        height: f64,
+    },
+    Focused(bool),
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    /// From wheels that scroll by lines.
+    ScrollLines {
+        x: f32,
+        y: f32,
+    },
+    /// From touchpads and the like.
+    ScrollPixels {
+        x: f64,
+        y: f64,
+    },
+    /// Raw mouse movement, see `UserInput::mouse_motion`.
+    MouseMotion {
+        x: f64,
+        y: f64,
+    },
+    /// `key` is `None` for keys winit has no name for. Held keys repeat `pressed` events.
+    Key {
+        key: Option<VirtualKeyCode>,
+        scancode: u32,
+        pressed: bool,
+        modifiers: ModifiersState,
+    },
+    /// Text typed into the window, after the keyboard layout and dead keys.
+    Character(char),
+    /// The window moved to a monitor with a different scale, or the scale changed. A
+    /// `Resized` with the new logical size usually follows.
+    HiDpiFactorChanged(f64),
+}
+
+impl InputEvent {
+    /// `None` for the winit events nothing here uses.
+    pub fn from_winit(event: Event) -> Option<Self> {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => Some(InputEvent::CloseRequested),
+                WindowEvent::Resized(logical) => Some(InputEvent::Resized {
+                    width: logical.width,
+                    height: logical.height,
+                }),
+                WindowEvent::Focused(focused) => Some(InputEvent::Focused(focused)),
+                WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved {
+                    x: position.x,
+                    y: position.y,
+                }),
+                WindowEvent::MouseInput { state, button, .. } => Some(InputEvent::MouseButton {
+                    button,
+                    pressed: state == ElementState::Pressed,
+                }),
+                WindowEvent::MouseWheel { delta, .. } => Some(match delta {
+                    MouseScrollDelta::LineDelta(x, y) => InputEvent::ScrollLines { x, y },
+                    MouseScrollDelta::PixelDelta(position) => InputEvent::ScrollPixels {
+                        x: position.x,
+                        y: position.y,
+                    },
+                }),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            scancode,
+                            state,
+                            virtual_keycode,
+                            modifiers,
+                        },
+                    ..
+                } => Some(InputEvent::Key {
+                    key: virtual_keycode,
+                    scancode,
+                    pressed: state == ElementState::Pressed,
+                    modifiers,
+                }),
+                WindowEvent::ReceivedCharacter(character) => Some(InputEvent::Character(character)),
+                WindowEvent::HiDpiFactorChanged(factor) => {
+                    Some(InputEvent::HiDpiFactorChanged(factor))
+                }
+                _ => None,
+            },
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => Some(InputEvent::MouseMotion {
+                x: delta.0,
+                y: delta.1,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// An event along with when it was polled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedEvent {
+    pub time: Instant,
+    pub event: InputEvent,
+}
+
+/// What happened since the last poll. Held keys carry over between polls, so keep one around
+/// and poll it every frame.
+#[derive(Debug, Clone)]
 pub struct UserInput {
+    /// Every event of the last poll, oldest first. The fields below only sum them up.
+    pub events: Vec<TimedEvent>,
+    /// Time since the poll before, zero on the first one.
+    pub frame_time: Duration,
+    /// The window needs drawing again, or a `RedrawRequester` asked for it. Not recorded.
+    pub redraw_requested: bool,
+    last_poll: Option<Instant>,
     pub end_requested: bool,
+    /// Logical size.
     pub new_frame_size: Option<(f64, f64)>,
+    /// Logical position.
     pub new_mouse_position: Option<(f64, f64)>,
+    /// Physical pixels per logical one. It starts out at 1, the `Runner` sets it from the
+    /// window before the first poll.
+    pub hidpi_factor: f64,
+    pub new_hidpi_factor: Option<f64>,
+    /// Wheel movement from wheels that scroll by lines, summed over the frame.
+    pub scroll_lines: (f32, f32),
+    /// Wheel movement from touchpads and the like, in logical pixels, summed over the frame.
+    pub scroll_pixels: (f64, f64),
+    /// Raw mouse movement summed over the frame. It isn't clamped to the window or scaled like
+    /// the cursor, so it keeps going while the cursor is stuck at the edge of the screen.
+    pub mouse_motion: (f64, f64),
+    /// As of the last keyboard event.
+    pub modifiers: ModifiersState,
+    /// While another window has the focus.
+    pub unfocused: bool,
+    keys_down: HashSet<VirtualKeyCode>,
+    keys_pressed: HashSet<VirtualKeyCode>,
+    keys_released: HashSet<VirtualKeyCode>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+}
+
+impl Default for UserInput {
+    fn default() -> Self {
+        Self {
+            events: vec![],
+            frame_time: Duration::from_secs(0),
+            redraw_requested: false,
+            last_poll: None,
+            end_requested: false,
+            new_frame_size: None,
+            new_mouse_position: None,
+            hidpi_factor: 1.0,
+            new_hidpi_factor: None,
+            scroll_lines: (0.0, 0.0),
+            scroll_pixels: (0.0, 0.0),
+            mouse_motion: (0.0, 0.0),
+            modifiers: ModifiersState::default(),
+            unfocused: false,
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+        }
+    }
 }
 
 impl UserInput {
-    pub fn poll_events_loop(events_loop: &mut EventsLoop) -> Self {
-        let mut output = UserInput::default();
-        events_loop.poll_events(|event| match event {
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            } => output.end_requested = true,
-            Event::WindowEvent {
-                event: WindowEvent::Resized(logical),
-                ..
-            } => output.new_frame_size = dbg!(Some((logical.width, logical.height))),
-            Event::WindowEvent {
-                event: WindowEvent::CursorMoved { position, .. },
-                ..
-            } => output.new_mouse_position = Some((position.x, position.y)),
-            _ => {}
+    pub fn poll_events_loop(&mut self, events_loop: &mut EventsLoop) {
+        let mut events = std::mem::replace(&mut self.events, vec![]);
+        events.clear();
+        let mut redraw_requested = false;
+        events_loop.poll_events(|event| {
+            collect(event, &mut events, &mut redraw_requested);
         });
+        self.finish_poll(events, redraw_requested);
+    }
+
+    /// Like `poll_events_loop`, but sleeps until there's some input to the window or a redraw
+    /// request. Raw device events come in whether the window has the focus or not, so they
+    /// don't wake it, and the ones that come in while it sleeps are dropped.
+    pub fn wait_events_loop(&mut self, events_loop: &mut EventsLoop) {
+        let mut events = std::mem::replace(&mut self.events, vec![]);
+        events.clear();
+        let mut redraw_requested = false;
+        events_loop.run_forever(|event| {
+            if let Event::DeviceEvent { .. } = event {
+                ControlFlow::Continue
+            } else if collect(event, &mut events, &mut redraw_requested) {
+                ControlFlow::Break
+            } else {
+                ControlFlow::Continue
+            }
+        });
+        // Along with whatever came in at the same time
+        events_loop.poll_events(|event| {
+            collect(event, &mut events, &mut redraw_requested);
+        });
+        self.finish_poll(events, redraw_requested);
+    }
+
+    fn finish_poll(&mut self, events: Vec<TimedEvent>, redraw_requested: bool) {
+        let now = Instant::now();
+        let frame_time = self.last_poll.map_or(Duration::from_secs(0), |last| now - last);
+        self.last_poll = Some(now);
+        self.replay(frame_time, events);
+        self.redraw_requested = redraw_requested;
+    }
+
+    /// Makes `events` the events of a new frame `frame_time` after the last one, as if they
+    /// had been polled.
+    pub fn replay(&mut self, frame_time: Duration, events: Vec<TimedEvent>) {
+        self.frame_time = frame_time;
+        self.redraw_requested = false;
+        self.end_requested = false;
+        self.new_frame_size = None;
+        self.new_mouse_position = None;
+        self.new_hidpi_factor = None;
+        self.scroll_lines = (0.0, 0.0);
+        self.scroll_pixels = (0.0, 0.0);
+        self.mouse_motion = (0.0, 0.0);
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+
+        for timed in &events {
+            self.apply_event(timed.event);
+        }
+        self.events = events;
+    }
+
+    /// `new_frame_size` or `new_hidpi_factor`, either means a new swapchain.
+    pub fn resized(&self) -> bool {
+        self.new_frame_size.is_some() || self.new_hidpi_factor.is_some()
+    }
+
+    /// True while `key` is held.
+    pub fn is_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// True if `key` went down since the last poll. Key repeats don't count.
+    pub fn just_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    /// True if `key` went up since the last poll.
+    pub fn just_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn button_just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn button_just_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    fn apply_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::CloseRequested => self.end_requested = true,
+            InputEvent::Resized { width, height } => {
+                self.new_frame_size = Some((width, height))
+            }
+            // Releases don't arrive while another window has the focus
+            InputEvent::Focused(false) => {
+                self.unfocused = true;
+                self.keys_released.extend(self.keys_down.drain());
+                self.buttons_released.extend(self.buttons_down.drain());
+                self.modifiers = ModifiersState::default();
+            }
+            InputEvent::Focused(true) => self.unfocused = false,
+            InputEvent::CursorMoved { x, y } => self.new_mouse_position = Some((x, y)),
+            InputEvent::MouseButton { button, pressed } => {
+                if pressed {
+                    if self.buttons_down.insert(button) {
+                        self.buttons_pressed.insert(button);
+                    }
+                } else if self.buttons_down.remove(&button) {
+                    self.buttons_released.insert(button);
+                }
+            }
+            InputEvent::ScrollLines { x, y } => {
+                self.scroll_lines.0 += x;
+                self.scroll_lines.1 += y;
+            }
+            InputEvent::ScrollPixels { x, y } => {
+                self.scroll_pixels.0 += x;
+                self.scroll_pixels.1 += y;
+            }
+            InputEvent::MouseMotion { x, y } => {
+                self.mouse_motion.0 += x;
+                self.mouse_motion.1 += y;
+            }
+            InputEvent::Key {
+                key,
+                pressed,
+                modifiers,
+                ..
+            } => {
+                self.modifiers = modifiers;
+                let key = match key {
+                    Some(key) => key,
+                    None => return,
+                };
+                if pressed {
+                    if self.keys_down.insert(key) {
+                        self.keys_pressed.insert(key);
+                    }
+                } else if self.keys_down.remove(&key) {
+                    self.keys_released.insert(key);
+                }
+            }
+            InputEvent::Character(_) => {}
+            InputEvent::HiDpiFactorChanged(factor) => {
+                self.hidpi_factor = factor;
+                self.new_hidpi_factor = Some(factor);
+            }
+        }
+    }
+}
 
-        output
+/// Adds `event` to `events` if it's input, and notes redraw requests. False for the events
+/// that are neither.
+fn collect(event: Event, events: &mut Vec<TimedEvent>, redraw_requested: &mut bool) -> bool {
+    match event {
+        Event::Awakened
+        | Event::WindowEvent {
+            event: WindowEvent::Refresh,
+            ..
+        } => {
+            *redraw_requested = true;
+            true
+        }
+        event => match InputEvent::from_winit(event) {
+            Some(event) => {
+                events.push(TimedEvent {
+                    time: Instant::now(),
+                    event,
+                });
+                true
+            }
+            None => false,
+        },
     }
 }
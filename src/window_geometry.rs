@@ -0,0 +1,70 @@
+//! Remembers where the window was, so the next launch opens it in the same place. Kept as a
+//! small TOML file per window title under `nicegfx` in the user state directory.
+
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::files;
+use crate::winit_state::WindowMode;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    /// Physical position of the top left corner while windowed, so it can be checked against
+    /// the monitors before the window exists.
+    pub position: Option<(f64, f64)>,
+    /// Logical inner size while windowed.
+    pub size: (f64, f64),
+    pub mode: WindowMode,
+    /// Name of the monitor the window was on, indices change when monitors come and go.
+    pub monitor: Option<String>,
+}
+
+/// Where the geometry of windows titled `title` lives, `None` when there's no state directory
+/// to put it in.
+pub fn path(title: &str) -> Option<PathBuf> {
+    let name: String = title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    files::state_dir().map(|dir| dir.join(format!("{}.window.toml", name)))
+}
+
+/// `None` if nothing was saved yet or the file can't be read.
+pub fn load(path: &Path) -> Option<WindowGeometry> {
+    let source = std::fs::read_to_string(path).ok()?;
+    match toml::from_str(&source) {
+        Ok(geometry) => Some(geometry),
+        Err(e) => {
+            warn!("Ignoring {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+pub fn save(path: &Path, geometry: &WindowGeometry) -> io::Result<()> {
+    let source = toml::to_string(geometry).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    files::write_atomically(path, source.as_bytes())
+}
+
+/// Whether `point` is on the monitor with the top left corner and size of `monitor`. The right
+/// and bottom edges belong to the next one over.
+pub fn on_monitor(point: PhysicalPosition, monitor: (PhysicalPosition, PhysicalSize)) -> bool {
+    let (position, size) = monitor;
+    point.x >= position.x
+        && point.y >= position.y
+        && point.x < position.x + size.width
+        && point.y < position.y + size.height
+}
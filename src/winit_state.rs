@@ -1,35 +1,486 @@
-use winit::CreationError;
+use winit::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
 use winit::EventsLoop;
+use winit::Icon;
+use winit::MonitorId;
 use winit::Window;
 use winit::WindowBuilder;
 
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::window_geometry::{self, WindowGeometry};
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
 pub type WindowSize = (u32, u32);
 
 const WINDOW_NAME: &str = "NiceGfx Window";
+const DEFAULT_SIZE: LogicalSize = LogicalSize {
+    width: 800.0,
+    height: 600.0,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    Windowed,
+    /// Fullscreen the way the platform does it, at the current video mode of the monitor. winit
+    /// 0.18 can't list or switch video modes, so there's no exclusive fullscreen at another
+    /// resolution.
+    Fullscreen,
+    /// A window without decorations covering the whole monitor. Switches faster than
+    /// `Fullscreen` and other windows can still go on top of it.
+    Borderless,
+}
+
+/// How the window gets created. The default is a resizable, decorated 800x600 window.
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    pub title: String,
+    /// Inner size while windowed, 800x600 with `None`. Only the first time with
+    /// `remember_geometry`.
+    pub size: Option<LogicalSize>,
+    pub min_size: Option<LogicalSize>,
+    pub max_size: Option<LogicalSize>,
+    /// Of the top left corner while windowed, up to the platform with `None`.
+    pub position: Option<LogicalPosition>,
+    /// `Windowed` with `None`.
+    pub mode: Option<WindowMode>,
+    /// Index into `monitors` of the monitor to open the window on, centered while windowed
+    /// without a `position`. The primary one with `None` or an index out of range.
+    pub monitor: Option<usize>,
+    pub decorations: bool,
+    pub resizable: bool,
+    pub always_on_top: bool,
+    /// Any image the `image` crate can open.
+    pub icon: Option<PathBuf>,
+    /// Saves the position, size, mode and monitor of the window when it closes, and opens it
+    /// the same way the next time a window with this title does. The saved size replaces
+    /// `size`, the mode only goes where `mode` is left open, and the position and monitor only
+    /// without a `position` or `monitor`.
+    pub remember_geometry: bool,
+}
+
+impl WindowConfig {
+    pub fn new<T: Into<String>>(title: T, size: LogicalSize) -> Self {
+        Self {
+            title: title.into(),
+            size: Some(size),
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: WINDOW_NAME.to_owned(),
+            size: None,
+            min_size: None,
+            max_size: None,
+            position: None,
+            mode: None,
+            monitor: None,
+            decorations: true,
+            resizable: true,
+            always_on_top: false,
+            icon: None,
+            remember_geometry: false,
+        }
+    }
+}
+
+/// A monitor as `monitors` lists it. `size` is its current video mode, winit 0.18 doesn't tell
+/// the other ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// What `WindowConfig::monitor` takes.
+    pub index: usize,
+    pub name: Option<String>,
+    /// Of the top left corner on the desktop.
+    pub position: PhysicalPosition,
+    pub size: PhysicalSize,
+    pub hidpi_factor: f64,
+}
+
+impl fmt::Display for MonitorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}, {}x{} at {},{}, hidpi factor {}",
+            self.index,
+            self.name.as_ref().map_or("unnamed", |name| name.as_str()),
+            self.size.width,
+            self.size.height,
+            self.position.x,
+            self.position.y,
+            self.hidpi_factor
+        )
+    }
+}
 
 #[derive(Debug)]
 pub struct WinitState {
     pub events_loop: EventsLoop,
-    pub window: Window,
+    /// Shared with the render thread, if there is one.
+    pub window: Arc<Window>,
+    config: WindowConfig,
+    mode: WindowMode,
+    /// Where the window was before it last left `Windowed`, to go back there.
+    windowed_geometry: Option<(LogicalPosition, LogicalSize)>,
+    /// Where the geometry gets saved, if it's remembered.
+    geometry_path: Option<PathBuf>,
 }
 
 impl WinitState {
-    pub fn new<T: Into<String>>(title: T, size: WindowSize) -> Result<Self, CreationError> {
-        let events_loop = EventsLoop::new();
-        let window = WindowBuilder::new()
-            .with_title(title)
-            .with_dimensions(size.into())
-            .with_always_on_top(true)
-            .build(&events_loop);
-        window.map(|window| Self {
+    pub fn new<T: Into<String>>(title: T, size: WindowSize) -> Result<Self, String> {
+        Self::from_config(WindowConfig::new(title, size.into()), EventsLoop::new())
+    }
+
+    /// Opens the window `config` describes, windowed ones on a given `monitor` without a
+    /// `position` centered on it.
+    pub fn from_config(mut config: WindowConfig, events_loop: EventsLoop) -> Result<Self, String> {
+        let geometry_path = if config.remember_geometry {
+            window_geometry::path(&config.title)
+        } else {
+            None
+        };
+        let restored_position = match geometry_path
+            .as_ref()
+            .and_then(|path| window_geometry::load(path))
+        {
+            Some(geometry) => restore(&mut config, &geometry, &events_loop),
+            None => None,
+        };
+        let chosen_monitor = config
+            .monitor
+            .and_then(|index| events_loop.get_available_monitors().nth(index));
+        let center =
+            config.position.is_none() && restored_position.is_none() && chosen_monitor.is_some();
+        let monitor = chosen_monitor.or_else(|| primary_monitor(&events_loop));
+
+        let mut builder = WindowBuilder::new()
+            .with_title(config.title.clone())
+            .with_dimensions(config.size.unwrap_or(DEFAULT_SIZE))
+            .with_resizable(config.resizable)
+            .with_decorations(config.decorations)
+            .with_always_on_top(config.always_on_top);
+        if let Some(min_size) = config.min_size {
+            builder = builder.with_min_dimensions(min_size);
+        }
+        if let Some(max_size) = config.max_size {
+            builder = builder.with_max_dimensions(max_size);
+        }
+        if let Some(path) = &config.icon {
+            builder = builder.with_window_icon(Some(load_icon(path)?));
+        }
+        let window = builder
+            .build(&events_loop)
+            .map_err(|e| format!("Couldn't create the window: {}", e))?;
+        if let Some(position) = config.position {
+            window.set_position(position);
+        } else if let Some(position) = restored_position {
+            // winit turns logical positions into pixels with the factor of the window
+            window.set_position(position.to_logical(window.get_hidpi_factor()));
+        }
+
+        let mode = config.mode.unwrap_or(WindowMode::Windowed);
+        let mut winit_state = Self {
             events_loop,
-            window,
+            window: Arc::new(window),
+            config,
+            mode: WindowMode::Windowed,
+            windowed_geometry: None,
+            geometry_path,
+        };
+        if center {
+            if let Some(monitor) = &monitor {
+                winit_state.center_on(monitor);
+            }
+        }
+        winit_state.apply_mode(mode, monitor);
+        Ok(winit_state)
+    }
+
+    /// Physical pixels per logical one, of the monitor the window is on.
+    pub fn hidpi_factor(&self) -> f64 {
+        self.window.get_hidpi_factor()
+    }
+
+    /// The size of the window's client area, `None` once the window is gone.
+    pub fn logical_size(&self) -> Option<LogicalSize> {
+        self.window.get_inner_size()
+    }
+
+    /// The size the swapchain gets.
+    pub fn physical_size(&self) -> Option<PhysicalSize> {
+        physical_size(&self.window)
+    }
+
+    pub fn mode(&self) -> WindowMode {
+        self.mode
+    }
+
+    /// Switches to `mode` on the monitor the window is on now. The swapchain has to be
+    /// recreated after.
+    pub fn set_mode(&mut self, mode: WindowMode) {
+        let monitor = if self.events_loop.get_available_monitors().next().is_some() {
+            Some(self.window.get_current_monitor())
+        } else {
+            None
+        };
+        self.apply_mode(mode, monitor);
+    }
+
+    /// Every connected monitor, see `monitors`.
+    pub fn monitors(&self) -> Vec<MonitorInfo> {
+        monitors(&self.events_loop)
+    }
+
+    /// Moves the window to `monitor` of `monitors`, centered on it while windowed and covering
+    /// it otherwise. The swapchain has to be recreated after.
+    pub fn move_to_monitor(&mut self, monitor: &MonitorInfo) -> Result<(), String> {
+        let monitor_id = self
+            .events_loop
+            .get_available_monitors()
+            .nth(monitor.index)
+            .filter(|id| id.get_name() == monitor.name && id.get_position() == monitor.position)
+            .ok_or_else(|| format!("Monitor {} isn't connected anymore", monitor))?;
+        match self.mode {
+            WindowMode::Windowed => self.center_on(&monitor_id),
+            WindowMode::Fullscreen => self.window.set_fullscreen(Some(monitor_id)),
+            WindowMode::Borderless => self.cover(&monitor_id),
+        }
+        Ok(())
+    }
+
+    /// Moves the window on to the monitor after the one it's on, see `next_monitor`. Nothing
+    /// happens without monitors, false then.
+    pub fn move_to_next_monitor(&mut self) -> Result<bool, String> {
+        let monitors = self.monitors();
+        if monitors.is_empty() {
+            return Ok(false);
+        }
+        let current = self.window.get_current_monitor().get_position();
+        let current = monitors
+            .iter()
+            .position(|monitor| monitor.position == current);
+        match next_monitor(&monitors, current) {
+            Some(monitor) => self.move_to_monitor(monitor).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Switches to `mode`, or back to `Windowed` if it's already in it.
+    pub fn toggle_mode(&mut self, mode: WindowMode) {
+        if self.mode == mode {
+            self.set_mode(WindowMode::Windowed);
+        } else {
+            self.set_mode(mode);
+        }
+    }
+
+    fn apply_mode(&mut self, mode: WindowMode, monitor: Option<MonitorId>) {
+        if mode == self.mode {
+            return;
+        }
+        match mode {
+            WindowMode::Windowed => {
+                self.window.set_fullscreen(None);
+                self.window.set_decorations(self.config.decorations);
+                if let Some((position, size)) = self.windowed_geometry.take() {
+                    self.window.set_position(position);
+                    self.window.set_inner_size(size);
+                }
+            }
+            WindowMode::Fullscreen | WindowMode::Borderless => {
+                let monitor = match monitor {
+                    Some(monitor) => monitor,
+                    None => {
+                        warn!("There's no monitor to go {:?} on", mode);
+                        return;
+                    }
+                };
+                if self.mode == WindowMode::Windowed {
+                    let position = self.window.get_position();
+                    let size = self.window.get_inner_size();
+                    self.windowed_geometry =
+                        position.and_then(|position| size.map(|size| (position, size)));
+                }
+                if mode == WindowMode::Fullscreen {
+                    self.window.set_fullscreen(Some(monitor));
+                } else {
+                    self.window.set_fullscreen(None);
+                    self.window.set_decorations(false);
+                    self.cover(&monitor);
+                }
+            }
+        }
+        self.mode = mode;
+    }
+
+    /// What gets saved for the next launch, `None` once the window is gone.
+    fn geometry(&self) -> Option<WindowGeometry> {
+        let (position, size) = match (self.mode, self.windowed_geometry) {
+            (WindowMode::Windowed, _) => {
+                (self.window.get_position(), self.window.get_inner_size()?)
+            }
+            (_, Some((position, size))) => (Some(position), size),
+            (_, None) => (
+                self.config.position,
+                self.config.size.unwrap_or(DEFAULT_SIZE),
+            ),
+        };
+        let hidpi_factor = self.hidpi_factor();
+        let monitor = if self.events_loop.get_available_monitors().next().is_some() {
+            self.window.get_current_monitor().get_name()
+        } else {
+            None
+        };
+        Some(WindowGeometry {
+            position: position.map(|position| position.to_physical(hidpi_factor).into()),
+            size: size.into(),
+            mode: self.mode,
+            monitor,
         })
     }
+
+    /// Moves the window to the middle of `monitor`, keeping its size.
+    fn center_on(&self, monitor: &MonitorId) {
+        let size = self
+            .window
+            .get_inner_size()
+            .unwrap_or_else(|| self.config.size.unwrap_or(DEFAULT_SIZE))
+            .to_physical(monitor.get_hidpi_factor());
+        let monitor_position = monitor.get_position();
+        let monitor_size = monitor.get_dimensions();
+        let position = PhysicalPosition::new(
+            monitor_position.x + (monitor_size.width - size.width).max(0.0) / 2.0,
+            monitor_position.y + (monitor_size.height - size.height).max(0.0) / 2.0,
+        );
+        self.window
+            .set_position(position.to_logical(self.hidpi_factor()));
+    }
+
+    /// Sizes the window to cover `monitor`.
+    fn cover(&self, monitor: &MonitorId) {
+        let hidpi_factor = monitor.get_hidpi_factor();
+        self.window
+            .set_position(monitor.get_position().to_logical(hidpi_factor));
+        self.window
+            .set_inner_size(monitor.get_dimensions().to_logical(hidpi_factor));
+    }
+}
+
+impl Drop for WinitState {
+    fn drop(&mut self) {
+        let path = match &self.geometry_path {
+            Some(path) => path,
+            None => return,
+        };
+        if let Some(geometry) = self.geometry() {
+            if let Err(e) = window_geometry::save(path, &geometry) {
+                warn!(
+                    "Couldn't save the window geometry to {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
 }
 
 impl Default for WinitState {
     fn default() -> Self {
-        Self::new(WINDOW_NAME, (800, 600)).expect("Failed to create a default window")
+        Self::from_config(WindowConfig::default(), EventsLoop::new())
+            .expect("Failed to create a default window")
     }
 }
+
+/// Every connected monitor, none on a headless X server and the like. Works without a window,
+/// which can't be created there.
+pub fn monitors(events_loop: &EventsLoop) -> Vec<MonitorInfo> {
+    events_loop
+        .get_available_monitors()
+        .enumerate()
+        .map(|(index, monitor)| MonitorInfo {
+            index,
+            name: monitor.get_name(),
+            position: monitor.get_position(),
+            size: monitor.get_dimensions(),
+            hidpi_factor: monitor.get_hidpi_factor(),
+        })
+        .collect()
+}
+
+/// The monitor after the one at `current` in `monitors`, going round to the first one. The
+/// first one if it's not known which one the window is on, `None` without any monitors.
+pub fn next_monitor(monitors: &[MonitorInfo], current: Option<usize>) -> Option<&MonitorInfo> {
+    let next = current.map_or(0, |current| (current + 1) % monitors.len().max(1));
+    monitors.get(next)
+}
+
+/// The size of `window`'s client area in pixels, `None` once the window is gone. For where
+/// there's only the window, like the render thread.
+pub fn physical_size(window: &Window) -> Option<PhysicalSize> {
+    window
+        .get_inner_size()
+        .map(|logical| logical.to_physical(window.get_hidpi_factor()))
+}
+
+/// Opens the window the way `geometry` says, as far as `config` lets it, see
+/// `WindowConfig::remember_geometry`. Returns the saved position in pixels unless it's off
+/// every monitor connected now. It can only be made logical once the window exists.
+fn restore(
+    config: &mut WindowConfig,
+    geometry: &WindowGeometry,
+    events_loop: &EventsLoop,
+) -> Option<PhysicalPosition> {
+    config.size = Some(geometry.size.into());
+    config.mode = config.mode.or(Some(geometry.mode));
+    // The saved spot would take the window away from the one the caller picked
+    if config.position.is_some() || config.monitor.is_some() {
+        return None;
+    }
+    let monitors: Vec<MonitorId> = events_loop.get_available_monitors().collect();
+    if let Some(name) = &geometry.monitor {
+        if let Some(index) = monitors
+            .iter()
+            .position(|monitor| monitor.get_name().as_ref() == Some(name))
+        {
+            config.monitor = Some(index);
+        }
+    }
+    let (x, y) = geometry.position?;
+    let position = PhysicalPosition::new(x, y);
+    let on_a_monitor = monitors.iter().any(|monitor| {
+        let rect = (monitor.get_position(), monitor.get_dimensions());
+        window_geometry::on_monitor(position, rect)
+    });
+    if !on_a_monitor {
+        info!("The saved window position is off every monitor, ignoring it");
+        return None;
+    }
+    Some(position)
+}
+
+/// `EventsLoop::get_primary_monitor` can panic without any monitors.
+fn primary_monitor(events_loop: &EventsLoop) -> Option<MonitorId> {
+    if events_loop.get_available_monitors().next().is_some() {
+        Some(events_loop.get_primary_monitor())
+    } else {
+        None
+    }
+}
+
+fn load_icon(path: &Path) -> Result<Icon, String> {
+    let error =
+        |e: &dyn std::fmt::Display| format!("Couldn't load the icon {}: {}", path.display(), e);
+    let image = image::open(path).map_err(|e| error(&e))?.to_rgba();
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).map_err(|e| error(&e))
+}
@@ -0,0 +1,18 @@
+//! The modules of the build script, so their unit tests run with `cargo test`. Cargo doesn't
+//! test build scripts themselves.
+
+// Most of what they have is only used by the build script
+#![allow(dead_code)]
+
+#[path = "../build/bindings.rs"]
+mod bindings;
+#[path = "../build/cache.rs"]
+mod cache;
+#[path = "../build/diagnostics.rs"]
+mod diagnostics;
+#[path = "../build/preprocess.rs"]
+mod preprocess;
+#[path = "../build/reflect.rs"]
+mod reflect;
+#[path = "../build/validate.rs"]
+mod validate;
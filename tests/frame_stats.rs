@@ -0,0 +1,60 @@
+use nicegfx::FrameStats;
+
+use std::time::Duration;
+
+fn ms(milliseconds: u64) -> Duration {
+    Duration::from_millis(milliseconds)
+}
+
+#[test]
+fn no_frames_yet_is_all_zero() {
+    let stats = FrameStats::default();
+    assert_eq!(stats.mean(), ms(0));
+    assert_eq!(stats.min(), ms(0));
+    assert_eq!(stats.max(), ms(0));
+    assert_eq!(stats.jitter(), ms(0));
+    assert_eq!(stats.fps(), 0.0);
+}
+
+#[test]
+fn stats_follow_the_frame_times() {
+    let mut stats = FrameStats::default();
+    for &frame_time in &[10, 20, 30] {
+        stats.push(ms(frame_time));
+    }
+    assert_eq!(stats.mean(), ms(20));
+    assert_eq!(stats.min(), ms(10));
+    assert_eq!(stats.max(), ms(30));
+    assert!((stats.fps() - 50.0).abs() < 1e-9);
+    // Both outer frames are 10ms off the mean, so the deviation is 10ms * sqrt(2/3)
+    let jitter = f64::from(stats.jitter().subsec_nanos()) / 1e6;
+    assert!((jitter - 10.0 * (2.0f64 / 3.0).sqrt()).abs() < 1e-3);
+
+    let mut steady = FrameStats::default();
+    for _ in 0..10 {
+        steady.push(ms(16));
+    }
+    assert_eq!(steady.jitter(), ms(0));
+}
+
+#[test]
+fn only_the_last_240_frames_count() {
+    let mut stats = FrameStats::default();
+    for _ in 0..240 {
+        stats.push(ms(10));
+    }
+    assert_eq!(stats.mean(), ms(10));
+    assert_eq!(stats.max(), ms(10));
+
+    // A full window drops its oldest frame for every new one
+    stats.push(ms(250));
+    assert_eq!(stats.max(), ms(250));
+    assert_eq!(stats.mean(), ms(11));
+
+    for _ in 0..240 {
+        stats.push(ms(20));
+    }
+    assert_eq!(stats.min(), ms(20));
+    assert_eq!(stats.max(), ms(20));
+    assert_eq!(stats.mean(), ms(20));
+}
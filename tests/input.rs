@@ -0,0 +1,279 @@
+use nicegfx::input_map::{self, Binding, InputMap, WheelDirection};
+use nicegfx::input_record::{Recorder, Replay};
+use nicegfx::user_input::{InputEvent, TimedEvent, UserInput};
+use nicegfx::LocalState;
+
+use winit::{ModifiersState, MouseButton, VirtualKeyCode};
+
+use std::time::{Duration, Instant};
+
+const FRAME: Duration = Duration::from_millis(16);
+
+fn timed(event: InputEvent) -> TimedEvent {
+    TimedEvent {
+        time: Instant::now(),
+        event,
+    }
+}
+
+fn key(key: VirtualKeyCode, pressed: bool) -> TimedEvent {
+    timed(InputEvent::Key {
+        key: Some(key),
+        scancode: 0,
+        pressed,
+        modifiers: ModifiersState::default(),
+    })
+}
+
+#[test]
+fn keys_are_held_between_frames_with_edges_on_one() {
+    let mut input = UserInput::default();
+    input.replay(FRAME, vec![key(VirtualKeyCode::W, true)]);
+    assert!(input.is_down(VirtualKeyCode::W));
+    assert!(input.just_pressed(VirtualKeyCode::W));
+
+    // A key repeat isn't a new press
+    input.replay(FRAME, vec![key(VirtualKeyCode::W, true)]);
+    assert!(input.is_down(VirtualKeyCode::W));
+    assert!(!input.just_pressed(VirtualKeyCode::W));
+
+    input.replay(FRAME, vec![key(VirtualKeyCode::W, false)]);
+    assert!(!input.is_down(VirtualKeyCode::W));
+    assert!(input.just_released(VirtualKeyCode::W));
+
+    input.replay(FRAME, vec![]);
+    assert!(!input.just_released(VirtualKeyCode::W));
+}
+
+#[test]
+fn losing_the_focus_releases_everything() {
+    let mut input = UserInput::default();
+    input.replay(
+        FRAME,
+        vec![
+            key(VirtualKeyCode::A, true),
+            timed(InputEvent::MouseButton {
+                button: MouseButton::Left,
+                pressed: true,
+            }),
+        ],
+    );
+    input.replay(FRAME, vec![timed(InputEvent::Focused(false))]);
+    assert!(!input.is_down(VirtualKeyCode::A));
+    assert!(input.just_released(VirtualKeyCode::A));
+    assert!(!input.is_button_down(MouseButton::Left));
+    assert!(input.button_just_released(MouseButton::Left));
+}
+
+#[test]
+fn every_event_of_a_frame_is_kept_and_summed() {
+    let mut input = UserInput::default();
+    let events = vec![
+        timed(InputEvent::ScrollLines { x: 0.0, y: 1.0 }),
+        timed(InputEvent::ScrollLines { x: 0.0, y: 2.0 }),
+        timed(InputEvent::MouseMotion { x: 3.0, y: -1.0 }),
+        timed(InputEvent::MouseMotion { x: 1.0, y: -1.0 }),
+        timed(InputEvent::Resized {
+            width: 640.0,
+            height: 480.0,
+        }),
+        timed(InputEvent::Resized {
+            width: 800.0,
+            height: 600.0,
+        }),
+    ];
+    input.replay(FRAME, events.clone());
+    assert_eq!(input.events, events);
+    assert_eq!(input.frame_time, FRAME);
+    assert_eq!(input.scroll_lines, (0.0, 3.0));
+    assert_eq!(input.mouse_motion, (4.0, -2.0));
+    assert_eq!(input.new_frame_size, Some((800.0, 600.0)));
+
+    input.replay(FRAME, vec![]);
+    assert!(input.events.is_empty());
+    assert_eq!(input.scroll_lines, (0.0, 0.0));
+    assert_eq!(input.new_frame_size, None);
+}
+
+#[test]
+fn hidpi_factor_changes_carry_over_and_count_as_resizes() {
+    let mut input = UserInput::default();
+    assert_eq!(input.hidpi_factor, 1.0);
+    input.replay(FRAME, vec![timed(InputEvent::HiDpiFactorChanged(2.0))]);
+    assert_eq!(input.hidpi_factor, 2.0);
+    assert_eq!(input.new_hidpi_factor, Some(2.0));
+    assert!(input.resized());
+
+    input.replay(FRAME, vec![]);
+    assert_eq!(input.hidpi_factor, 2.0);
+    assert!(!input.resized());
+}
+
+#[test]
+fn local_state_scales_sizes_and_positions_to_pixels() {
+    let input_map = InputMap::from_toml(input_map::DEFAULT_BINDINGS).unwrap();
+    let mut input = UserInput::default();
+    input.replay(
+        FRAME,
+        vec![
+            timed(InputEvent::HiDpiFactorChanged(2.0)),
+            timed(InputEvent::Resized {
+                width: 400.0,
+                height: 300.0,
+            }),
+            timed(InputEvent::CursorMoved { x: 10.0, y: 20.0 }),
+        ],
+    );
+    let mut local_state = LocalState::default();
+    local_state.update_from_input(&input, &input_map);
+    assert_eq!((local_state.frame_width, local_state.frame_height), (400.0, 300.0));
+    assert_eq!(local_state.physical_frame_size(), (800.0, 600.0));
+    assert_eq!(local_state.physical_mouse_position(), (20.0, 40.0));
+
+    // The size stays until the next resize
+    input.replay(FRAME, vec![]);
+    local_state.update_from_input(&input, &input_map);
+    assert_eq!(local_state.physical_frame_size(), (800.0, 600.0));
+}
+
+#[test]
+fn default_bindings_have_no_conflicts() {
+    let input_map = InputMap::from_toml(input_map::DEFAULT_BINDINGS).unwrap();
+    assert!(input_map.conflicts().is_empty());
+}
+
+#[test]
+fn bindings_survive_a_save_and_load() {
+    let mut input_map = InputMap::from_toml(input_map::DEFAULT_BINDINGS).unwrap();
+    input_map
+        .bind("back", Binding::Button(MouseButton::Other(4)))
+        .unwrap();
+    input_map
+        .bind_axis("pan", true, Binding::Wheel(WheelDirection::Right))
+        .unwrap();
+    input_map.bind("unbound", Binding::Key(VirtualKeyCode::F1)).unwrap();
+    input_map.unbind(Binding::Key(VirtualKeyCode::F1));
+
+    let source = input_map.to_toml().unwrap();
+    assert_eq!(InputMap::from_toml(&source).unwrap(), input_map);
+}
+
+#[test]
+fn inputs_bound_twice_are_refused() {
+    let source = r#"
+        [actions]
+        jump = [{ key = "Space" }]
+        fire = [{ key = "Space" }]
+    "#;
+    assert!(InputMap::from_toml(source).is_err());
+
+    let mut input_map = InputMap::default();
+    let space = Binding::Key(VirtualKeyCode::Space);
+    input_map.bind("jump", space).unwrap();
+    assert!(input_map.bind("jump", space).is_ok());
+    assert!(input_map.bind("fire", space).is_err());
+    assert!(input_map.bind_axis("zoom", true, space).is_err());
+
+    let wheel_up = Binding::Wheel(WheelDirection::Up);
+    input_map.bind_axis("zoom", true, wheel_up).unwrap();
+    assert!(input_map.bind_axis("zoom", true, wheel_up).is_ok());
+    assert!(input_map.bind_axis("zoom", false, wheel_up).is_err());
+    assert!(input_map.conflicts().is_empty());
+
+    input_map.unbind(space);
+    assert!(input_map.bind("fire", space).is_ok());
+}
+
+#[test]
+fn actions_follow_their_bindings() {
+    let mut input_map = InputMap::default();
+    input_map.bind("quit", Binding::Key(VirtualKeyCode::Escape)).unwrap();
+    input_map.bind("quit", Binding::Button(MouseButton::Right)).unwrap();
+
+    let mut input = UserInput::default();
+    input.replay(
+        FRAME,
+        vec![timed(InputEvent::MouseButton {
+            button: MouseButton::Right,
+            pressed: true,
+        })],
+    );
+    assert!(input_map.is_active(&input, "quit"));
+    assert!(input_map.just_activated(&input, "quit"));
+    assert!(!input_map.is_active(&input, "unbound"));
+
+    input.replay(FRAME, vec![]);
+    assert!(input_map.is_active(&input, "quit"));
+    assert!(!input_map.just_activated(&input, "quit"));
+}
+
+#[test]
+fn axes_combine_both_sides_and_the_wheel() {
+    let mut input_map = InputMap::default();
+    input_map
+        .bind_axis("move", true, Binding::Key(VirtualKeyCode::D))
+        .unwrap();
+    input_map
+        .bind_axis("move", false, Binding::Key(VirtualKeyCode::A))
+        .unwrap();
+    input_map
+        .bind_axis("zoom", true, Binding::Wheel(WheelDirection::Up))
+        .unwrap();
+
+    let mut input = UserInput::default();
+    input.replay(FRAME, vec![key(VirtualKeyCode::D, true)]);
+    assert_eq!(input_map.axis(&input, "move"), 1.0);
+    input.replay(FRAME, vec![key(VirtualKeyCode::A, true)]);
+    assert_eq!(input_map.axis(&input, "move"), 0.0);
+
+    input.replay(
+        FRAME,
+        vec![timed(InputEvent::ScrollLines { x: 0.0, y: 0.5 })],
+    );
+    assert_eq!(input_map.axis(&input, "zoom"), 0.5);
+    input.replay(
+        FRAME,
+        vec![timed(InputEvent::ScrollLines { x: 0.0, y: -0.5 })],
+    );
+    assert_eq!(input_map.axis(&input, "zoom"), 0.0);
+}
+
+#[test]
+fn replays_feed_back_what_was_recorded() {
+    let path = std::env::temp_dir().join(format!("nicegfx-{}.rec", std::process::id()));
+    let mut recorder = Recorder::create(&path).unwrap();
+
+    let frames = vec![
+        vec![key(VirtualKeyCode::W, true)],
+        vec![],
+        vec![
+            timed(InputEvent::CursorMoved { x: 10.0, y: 20.0 }),
+            timed(InputEvent::Character('w')),
+            key(VirtualKeyCode::W, false),
+        ],
+    ];
+    let mut recorded = UserInput::default();
+    for events in &frames {
+        recorded.replay(FRAME, events.clone());
+        recorder.record(&recorded).unwrap();
+    }
+    drop(recorder);
+
+    let mut replay = Replay::open(&path).unwrap();
+    let mut replayed = UserInput::default();
+    for events in &frames {
+        assert!(replay.next_frame(&mut replayed).unwrap());
+        let kinds: Vec<InputEvent> = replayed.events.iter().map(|e| e.event).collect();
+        let expected: Vec<InputEvent> = events.iter().map(|e| e.event).collect();
+        assert_eq!(kinds, expected);
+        assert_eq!(replayed.frame_time, FRAME);
+    }
+    // The end of the recording leaves the input of the last frame alone
+    assert!(!replay.next_frame(&mut replayed).unwrap());
+    assert_eq!(replayed.new_mouse_position, Some((10.0, 20.0)));
+    assert!(!replayed.is_down(VirtualKeyCode::W));
+    replayed.replay(FRAME, vec![]);
+    assert_eq!(replayed.new_mouse_position, None);
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,61 @@
+use nicegfx::pipeline_cache;
+
+use gfx_hal::adapter::{AdapterInfo, DeviceType};
+
+use std::path::PathBuf;
+
+fn adapter(vendor: usize, device: usize, name: &str) -> AdapterInfo {
+    AdapterInfo {
+        name: name.to_owned(),
+        vendor,
+        device,
+        device_type: DeviceType::DiscreteGpu,
+    }
+}
+
+fn temp_path(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "nicegfx-pipeline-cache-{}-{}",
+        std::process::id(),
+        test
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join("pipelines.bin")
+}
+
+#[test]
+fn caches_come_back_for_the_same_adapter() {
+    let path = temp_path("same");
+    let gpu = adapter(0x10de, 0x1b80, "GeForce GTX 1080");
+    assert_eq!(pipeline_cache::load(&path, &gpu), None);
+
+    // Newlines in the data don't get mistaken for the end of the header
+    let data = b"\x01\n\x02\n\x03";
+    pipeline_cache::save(&path, &gpu, data).unwrap();
+    assert_eq!(pipeline_cache::load(&path, &gpu).as_deref(), Some(&data[..]));
+    pipeline_cache::save(&path, &gpu, b"").unwrap();
+    assert_eq!(pipeline_cache::load(&path, &gpu), Some(vec![]));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn caches_of_other_adapters_are_ignored() {
+    let path = temp_path("other");
+    let gpu = adapter(0x10de, 0x1b80, "GeForce GTX 1080");
+    pipeline_cache::save(&path, &gpu, b"driver data").unwrap();
+
+    let other_vendor = adapter(0x1002, 0x1b80, "GeForce GTX 1080");
+    let other_device = adapter(0x10de, 0x1b81, "GeForce GTX 1080");
+    let other_name = adapter(0x10de, 0x1b80, "GeForce GTX 1080\nTi");
+    for other in &[other_vendor, other_device, other_name] {
+        assert_eq!(pipeline_cache::load(&path, other), None);
+    }
+    assert!(pipeline_cache::load(&path, &gpu).is_some());
+
+    // Nor does anything without a header
+    std::fs::write(&path, b"driver data").unwrap();
+    assert_eq!(pipeline_cache::load(&path, &gpu), None);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
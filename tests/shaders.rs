@@ -0,0 +1,156 @@
+use nicegfx::shaders::{self, DescriptorBinding, ShaderInterface, SpecConstants,
+                       SpecializationKey};
+
+use gfx_hal::pso::{DescriptorType, ShaderStageFlags};
+
+fn interface(
+    stage: ShaderStageFlags,
+    descriptor_bindings: &'static [DescriptorBinding],
+) -> ShaderInterface {
+    ShaderInterface {
+        name: "test",
+        stage,
+        inputs: &[],
+        outputs: &[],
+        descriptor_bindings,
+        push_constants: None,
+    }
+}
+
+const CHANNELS: DescriptorBinding = DescriptorBinding {
+    name: "channels",
+    set: 0,
+    binding: 1,
+    ty: DescriptorType::CombinedImageSampler,
+    count: 4,
+};
+
+const UNIFORMS: DescriptorBinding = DescriptorBinding {
+    name: "uniforms",
+    set: 0,
+    binding: 0,
+    ty: DescriptorType::UniformBuffer,
+    count: 1,
+};
+
+#[test]
+fn spec_constants_are_keyed_by_their_values_not_their_order() {
+    let a = SpecConstants::default().with(1, 2u32).with(0, true);
+    let b = SpecConstants::default().with(0, true).with(1, 2u32);
+    assert_eq!(a, b);
+    assert_ne!(a, SpecConstants::default().with(0, true).with(1, 3u32));
+
+    let mut c = SpecConstants::default().with(0, false);
+    c.set(1, 2u32);
+    c.set(0, true);
+    assert_eq!(a, c);
+}
+
+#[test]
+fn spec_constants_point_into_their_data() {
+    let constants = SpecConstants::default().with(3, 1.5f32).with(1, -1i32);
+    let (entries, data) = constants.specialization();
+    assert_eq!(data.len(), 8);
+    assert_eq!(entries[0].id, 1);
+    assert_eq!(entries[1].id, 3);
+    let value = |range: &std::ops::Range<u16>| {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&data[range.start as usize..range.end as usize]);
+        u32::from_ne_bytes(bytes)
+    };
+    assert_eq!(value(&entries[0].range), -1i32 as u32);
+    assert_eq!(value(&entries[1].range), 1.5f32.to_bits());
+}
+
+#[test]
+fn the_default_key_specializes_nothing() {
+    let key = SpecializationKey::default();
+    assert!(key.vertex.specialization().0.is_empty());
+    assert!(key.fragment.specialization().1.is_empty());
+}
+
+#[test]
+fn textures_only_go_to_reflected_samplers() {
+    let vertex = interface(ShaderStageFlags::VERTEX, &[UNIFORMS]);
+    let fragment = interface(ShaderStageFlags::FRAGMENT, &[CHANNELS]);
+    let stages = [&vertex, &fragment];
+    assert_eq!(shaders::sampler_binding(&stages, (0, 1)).unwrap().count, 4);
+    assert!(shaders::sampler_binding(&stages, (0, 0)).is_err());
+    assert!(shaders::sampler_binding(&stages, (0, 2)).is_err());
+    assert!(shaders::sampler_binding(&stages, (1, 1)).is_err());
+}
+
+#[test]
+fn stages_sharing_a_binding_get_one_layout_binding() {
+    let vertex = interface(ShaderStageFlags::VERTEX, &[UNIFORMS]);
+    let fragment = interface(ShaderStageFlags::FRAGMENT, &[UNIFORMS, CHANNELS]);
+    let sets = shaders::set_layout_bindings(&[&vertex, &fragment]).unwrap();
+    assert_eq!(sets.len(), 1);
+    assert_eq!(sets[0].len(), 2);
+    assert_eq!(
+        sets[0][0].stage_flags,
+        ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT
+    );
+    assert_eq!(sets[0][1].stage_flags, ShaderStageFlags::FRAGMENT);
+}
+
+#[test]
+fn stages_disagreeing_on_a_binding_are_rejected() {
+    const SAMPLER: DescriptorBinding = DescriptorBinding {
+        name: "sampler",
+        ty: DescriptorType::CombinedImageSampler,
+        ..UNIFORMS
+    };
+    let vertex = ShaderInterface {
+        name: "a.vert",
+        ..interface(ShaderStageFlags::VERTEX, &[UNIFORMS])
+    };
+    let fragment = ShaderInterface {
+        name: "a.frag",
+        ..interface(ShaderStageFlags::FRAGMENT, &[SAMPLER])
+    };
+    let error = shaders::set_layout_bindings(&[&vertex, &fragment]).unwrap_err();
+    assert!(error.contains("a.vert") && error.contains("a.frag"), "{}", error);
+
+    const ARRAY: DescriptorBinding = DescriptorBinding {
+        count: 2,
+        ..UNIFORMS
+    };
+    let fragment = interface(ShaderStageFlags::FRAGMENT, &[ARRAY]);
+    assert!(shaders::set_layout_bindings(&[&vertex, &fragment]).is_err());
+}
+
+#[test]
+fn push_constants_get_pushed_to_every_stage_covering_them() {
+    let with_push_constants = |stage, range| ShaderInterface {
+        push_constants: Some(range),
+        ..interface(stage, &[])
+    };
+    let vertex = with_push_constants(ShaderStageFlags::VERTEX, 0..16);
+    let fragment = with_push_constants(ShaderStageFlags::FRAGMENT, 8..32);
+    assert_eq!(
+        shaders::push_constant_updates(&[&vertex, &fragment]),
+        [
+            (ShaderStageFlags::VERTEX, 0..8),
+            (ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT, 8..16),
+            (ShaderStageFlags::FRAGMENT, 16..32),
+        ]
+    );
+
+    // A shared block goes in one piece, and a gap between blocks isn't pushed at all
+    let fragment = with_push_constants(ShaderStageFlags::FRAGMENT, 0..16);
+    assert_eq!(
+        shaders::push_constant_updates(&[&vertex, &fragment]),
+        [(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT, 0..16)]
+    );
+    let fragment = with_push_constants(ShaderStageFlags::FRAGMENT, 32..48);
+    assert_eq!(
+        shaders::push_constant_updates(&[&vertex, &fragment]),
+        [
+            (ShaderStageFlags::VERTEX, 0..16),
+            (ShaderStageFlags::FRAGMENT, 32..48),
+        ]
+    );
+    let without = interface(ShaderStageFlags::VERTEX, &[]);
+    assert!(shaders::push_constant_updates(&[&without]).is_empty());
+}
@@ -0,0 +1,33 @@
+use nicegfx::timestep::FixedTimestep;
+
+use std::time::Duration;
+
+fn ms(milliseconds: u64) -> Duration {
+    Duration::from_millis(milliseconds)
+}
+
+#[test]
+fn whole_steps_run_and_the_rest_carries_over() {
+    let mut timestep = FixedTimestep::new(ms(10), 8);
+    assert_eq!(timestep.advance(ms(5)).count(), 0);
+    assert!((timestep.render_time().alpha - 0.5).abs() < 1e-6);
+
+    let steps: Vec<_> = timestep.advance(ms(25)).collect();
+    assert_eq!(steps.len(), 3);
+    assert_eq!(steps[0].total, ms(10));
+    assert_eq!(steps[2].total, ms(30));
+    assert!(steps.iter().all(|step| step.step == ms(10) && step.frame_time == ms(25)));
+
+    let render_time = timestep.render_time();
+    assert_eq!(render_time.total, ms(30));
+    assert_eq!(render_time.frame_time, ms(25));
+    assert!(render_time.alpha.abs() < 1e-6);
+}
+
+#[test]
+fn slow_frames_only_catch_up_so_far() {
+    let mut timestep = FixedTimestep::new(ms(10), 4);
+    assert_eq!(timestep.advance(ms(1000)).count(), 4);
+    assert_eq!(timestep.render_time().total, ms(40));
+    assert_eq!(timestep.advance(ms(10)).count(), 1);
+}
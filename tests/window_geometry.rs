@@ -0,0 +1,95 @@
+use nicegfx::window_geometry::{self, WindowGeometry};
+use nicegfx::winit_state;
+use nicegfx::{MonitorInfo, WindowMode};
+
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+fn monitor(x: f64, y: f64, width: f64, height: f64) -> (PhysicalPosition, PhysicalSize) {
+    (
+        PhysicalPosition::new(x, y),
+        PhysicalSize::new(width, height),
+    )
+}
+
+fn on_monitor(x: f64, y: f64, monitor: (PhysicalPosition, PhysicalSize)) -> bool {
+    window_geometry::on_monitor(PhysicalPosition::new(x, y), monitor)
+}
+
+#[test]
+fn positions_on_a_monitor() {
+    let primary = monitor(0.0, 0.0, 1920.0, 1080.0);
+    assert!(on_monitor(0.0, 0.0, primary));
+    assert!(on_monitor(100.0, 200.0, primary));
+    assert!(on_monitor(1919.5, 1079.5, primary));
+
+    // The right and bottom edges are the next monitor's
+    assert!(!on_monitor(1920.0, 500.0, primary));
+    assert!(!on_monitor(500.0, 1080.0, primary));
+    assert!(!on_monitor(-1.0, 500.0, primary));
+    assert!(!on_monitor(500.0, -1.0, primary));
+}
+
+#[test]
+fn positions_on_a_monitor_left_of_the_primary_one() {
+    let left = monitor(-1280.0, 0.0, 1280.0, 1024.0);
+    assert!(on_monitor(-1280.0, 0.0, left));
+    assert!(on_monitor(-1.0, 1023.0, left));
+    assert!(!on_monitor(0.0, 0.0, left));
+    assert!(!on_monitor(-1281.0, 0.0, left));
+}
+
+#[test]
+fn next_monitor_goes_round() {
+    let monitors: Vec<MonitorInfo> = (0..3)
+        .map(|index| MonitorInfo {
+            index,
+            name: Some(format!("DP-{}", index)),
+            position: PhysicalPosition::new(1920.0 * index as f64, 0.0),
+            size: PhysicalSize::new(1920.0, 1080.0),
+            hidpi_factor: 1.0,
+        })
+        .collect();
+    let next = |current| winit_state::next_monitor(&monitors, current).map(|m| m.index);
+    assert_eq!(next(Some(0)), Some(1));
+    assert_eq!(next(Some(2)), Some(0));
+    // Off every known monitor
+    assert_eq!(next(None), Some(0));
+}
+
+#[test]
+fn next_monitor_without_monitors_is_none() {
+    // Headless X servers and the like have none
+    assert_eq!(winit_state::next_monitor(&[], None), None);
+    assert_eq!(winit_state::next_monitor(&[], Some(0)), None);
+}
+
+#[test]
+fn geometry_survives_a_save_and_load() {
+    let dir = std::env::temp_dir().join(format!("nicegfx-{}-geometry", std::process::id()));
+    let path = dir.join("window.toml");
+    assert_eq!(window_geometry::load(&path), None);
+
+    let geometry = WindowGeometry {
+        position: Some((-1200.0, 40.0)),
+        size: (1024.0, 768.5),
+        mode: WindowMode::Borderless,
+        monitor: Some("DP-1".to_owned()),
+    };
+    window_geometry::save(&path, &geometry).unwrap();
+    assert_eq!(window_geometry::load(&path), Some(geometry));
+
+    // Nothing's known about the position or monitor of a window that never was windowed
+    let geometry = WindowGeometry {
+        position: None,
+        size: (800.0, 600.0),
+        mode: WindowMode::Fullscreen,
+        monitor: None,
+    };
+    window_geometry::save(&path, &geometry).unwrap();
+    assert_eq!(window_geometry::load(&path), Some(geometry));
+
+    std::fs::write(&path, "size = \"big\"").unwrap();
+    assert_eq!(window_geometry::load(&path), None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}